@@ -0,0 +1,183 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::{Frame, layout::{Layout, Constraint}, style::{Style, Color, Modifier}, text::{Line, Span}, widgets::{Block, Borders, List, ListItem, ListState, Paragraph}};
+use the_lock_lib::directory_content::{DirectoryContent, DirectoryContentPath, SingleEncryptedFile};
+
+use crate::screen::TerminalGuard;
+
+/// What user asked for while browsing an archive
+pub enum BrowserAction {
+    Decrypt(DirectoryContentPath),
+    Verify(DirectoryContentPath),
+    Remove(Vec<DirectoryContentPath>),
+    Move(DirectoryContentPath),
+}
+
+enum RowKind<'a> {
+    Directory { expanded: bool },
+    File(&'a SingleEncryptedFile),
+}
+
+struct Row<'a> {
+    path: DirectoryContentPath,
+    name: &'a str,
+    depth: usize,
+    kind: RowKind<'a>,
+}
+
+fn rows_helper<'a>(content: &'a DirectoryContent, prefix: &DirectoryContentPath, expanded: &HashSet<String>, rows: &mut Vec<Row<'a>>) {
+    for (name, dir) in content.get_dir_iter() {
+        let mut path = prefix.clone();
+        path.push(name).expect("Names are not empty");
+        let is_expanded = expanded.contains(&path.to_string());
+        rows.push(Row { path: path.clone(), name, depth: prefix.len(), kind: RowKind::Directory { expanded: is_expanded } });
+        if is_expanded {
+            rows_helper(dir, &path, expanded, rows);
+        }
+    }
+    for (name, file) in content.get_files_iter() {
+        let mut path = prefix.clone();
+        path.push(name).expect("Names are not empty");
+        rows.push(Row { path, name, depth: prefix.len(), kind: RowKind::File(file) });
+    }
+}
+
+#[inline]
+fn rows<'a>(content: &'a DirectoryContent, expanded: &HashSet<String>) -> Vec<Row<'a>> {
+    let mut rows = Vec::new();
+    rows_helper(content, &DirectoryContentPath::default(), expanded, &mut rows);
+    rows
+}
+
+#[inline]
+fn flag(name: &'static str, value: bool) -> Span<'static> {
+    Span::styled(name, match value {
+        true => Style::new().fg(Color::Green),
+        false => Style::new().fg(Color::Red).add_modifier(Modifier::CROSSED_OUT),
+    })
+}
+
+fn row_line<'a>(row: &Row<'a>, marked: bool) -> Line<'a> {
+    let mut spans = vec![Span::raw("  ".repeat(row.depth))];
+    match row.kind {
+        RowKind::Directory { expanded } => {
+            spans.push(Span::raw(if expanded { "▾ " } else { "▸ " }));
+            spans.push(Span::styled(row.name, Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD)));
+        }
+        RowKind::File(file) => {
+            spans.push(Span::raw("  "));
+            spans.push(Span::raw(row.name));
+            spans.push(Span::raw(" ["));
+            spans.push(flag("content", file.has_content()));
+            spans.push(Span::raw(" "));
+            spans.push(flag("key", file.has_key()));
+            spans.push(Span::raw(" "));
+            spans.push(flag("digest", file.has_digest()));
+            spans.push(Span::raw(" "));
+            spans.push(flag("signed", file.is_signed()));
+            spans.push(Span::raw("]"));
+        }
+    }
+    if marked {
+        spans.push(Span::styled(" ✗ to remove", Style::new().fg(Color::Red).add_modifier(Modifier::BOLD)));
+    }
+    Line::from(spans)
+}
+
+const HELP: &str = "↑/↓ move  →/Enter expand  ← collapse  d decrypt  v verify  m move  x mark for removal  r remove marked  q quit";
+
+fn draw(frame: &mut Frame, rows: &[Row], marked: &BTreeSet<String>, state: &mut ListState) {
+    let [tree_area, details_area, help_area] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(3),
+        Constraint::Length(1),
+    ]).areas(frame.size());
+    let items: Vec<ListItem> = rows.iter().map(|row| ListItem::new(row_line(row, marked.contains(&row.path.to_string())))).collect();
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(format!("Archive content ({} marked for removal)", marked.len())))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+        tree_area,
+        state,
+    );
+    let details = match state.selected().and_then(|pos| rows.get(pos)) {
+        Some(Row { path, kind: RowKind::File(file), .. }) => format!("{path} has_content: {}, has_key: {}, has_digest: {}, has_signature: {}", file.has_content(), file.has_key(), file.has_digest(), file.is_signed()),
+        Some(Row { path, .. }) => format!("{path}/"),
+        None => "Archive is empty".to_owned(),
+    };
+    frame.render_widget(Paragraph::new(details).block(Block::default().borders(Borders::ALL)), details_area);
+    frame.render_widget(Paragraph::new(HELP).style(Style::new().add_modifier(Modifier::DIM)), help_area);
+}
+
+/// Shows `content` as a collapsible tree until user picks an action or quits
+pub fn browse(content: &DirectoryContent) -> std::io::Result<Option<BrowserAction>> {
+    let mut guard = TerminalGuard::new()?;
+    let mut expanded = HashSet::new();
+    let mut marked = BTreeSet::new();
+    let mut state = ListState::default().with_selected(Some(0));
+    loop {
+        let rows = rows(content, &expanded);
+        if let Some(pos) = state.selected() {
+            state.select(Some(pos.min(rows.len().saturating_sub(1))));
+        }
+        guard.terminal.draw(|frame| draw(frame, &rows, &marked, &mut state))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let pos = state.selected().unwrap_or(0);
+        let selected = rows.get(pos);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => state.select(Some(pos.saturating_sub(1))),
+            KeyCode::Down | KeyCode::Char('j') => state.select(Some(pos + 1)),
+            KeyCode::Right | KeyCode::Enter | KeyCode::Char('l') => {
+                if let Some(Row { path, kind: RowKind::Directory { .. }, .. }) = selected {
+                    expanded.insert(path.to_string());
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') => match selected {
+                Some(Row { path, kind: RowKind::Directory { expanded: true }, .. }) => {
+                    expanded.remove(&path.to_string());
+                }
+                Some(Row { depth, .. }) => {
+                    if let Some(parent) = rows[..pos].iter().rposition(|row| row.depth < *depth) {
+                        state.select(Some(parent));
+                    }
+                }
+                None => (),
+            },
+            KeyCode::Char('x') | KeyCode::Char(' ') => {
+                if let Some(row) = selected {
+                    let path = row.path.to_string();
+                    if !marked.remove(&path) {
+                        marked.insert(path);
+                    }
+                }
+            }
+            KeyCode::Char('d') => {
+                if let Some(row) = selected {
+                    return Ok(Some(BrowserAction::Decrypt(row.path.clone())));
+                }
+            }
+            KeyCode::Char('v') => {
+                if let Some(row) = selected {
+                    return Ok(Some(BrowserAction::Verify(row.path.clone())));
+                }
+            }
+            KeyCode::Char('m') => {
+                if let Some(row) = selected {
+                    return Ok(Some(BrowserAction::Move(row.path.clone())));
+                }
+            }
+            KeyCode::Char('r') if !marked.is_empty() => {
+                return Ok(Some(BrowserAction::Remove(marked.iter().map(|path| DirectoryContentPath::from(path.as_str())).collect())));
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            _ => (),
+        }
+    }
+}
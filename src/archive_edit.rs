@@ -0,0 +1,336 @@
+use std::{collections::{HashMap, HashSet}, fs::File, io::{self, Read, Write}, path::Path};
+
+use sha2::{Digest, Sha256, Sha512};
+use the_lock_lib::{EncryptedFile, asymetric_key::{PrivateKey, PublicKey}, directory_content::{DirectoryContent, DirectoryContentPath}, error::{EncryptedFileError, EncryptedFileResult}, rsa::{Pss, RsaPrivateKey, rand_core::OsRng}};
+use zip::{ZipArchive, ZipWriter, result::ZipResult};
+
+use crate::archive_info::CONTENT_DIR;
+use crate::extraction::{entry_files, is_safe_entry};
+use crate::utils::PendingFile;
+
+/// Writes the archive without `to_delete` to a temporary file, lets `edit` change it and then replaces `path` with it
+/// `path` is left untouched if anything fails, on success the archive has to be reopened with `reopen`
+pub fn rewrite<R, F>(path: &Path, encrypted_file: &EncryptedFile, to_delete: &Vec<DirectoryContentPath>, edit: F) -> EncryptedFileResult<R>
+    where F: FnOnce(&mut EncryptedFile) -> EncryptedFileResult<R> {
+    let mut output = PendingFile::create(Box::from(path))?;
+    encrypted_file.delete_path(output.file(), to_delete)?;
+    let mut edited = EncryptedFile::new(output.path())?;
+    edited.set_zip_file_options(*encrypted_file.zip_file_options());
+    let ans = edit(&mut edited)?;
+    drop(edited);
+    output.persist()?;
+    Ok(ans)
+}
+
+/// Replaces `encrypted_file` with a fresh handle to `path` keeping its zip file options
+pub fn reopen(path: &Path, encrypted_file: &mut EncryptedFile) -> io::Result<()> {
+    let mut reopened = EncryptedFile::new(path)?;
+    reopened.set_zip_file_options(*encrypted_file.zip_file_options());
+    *encrypted_file = reopened;
+    Ok(())
+}
+
+/// Checks whether file or directory `from` can be moved to `to` without clashing with other entries
+/// `to` can't have `.` or `..` elements, such entry couldn't be decrypted back
+pub fn check_move(content: &DirectoryContent, from: &DirectoryContentPath, to: &DirectoryContentPath) -> EncryptedFileResult<()> {
+    check_entry(to)?;
+    if from.len() == 0 || (content.get_file(from).is_none() && content.get_dir(from).is_none()) {
+        return Err(EncryptedFileError::FileDoesNotExist);
+    }
+    if content.get_file(to).is_some() || content.get_dir(to).is_some() {
+        return Err(EncryptedFileError::FileAlreadyExists);
+    }
+    if to.len() == 0 || (from.len() <= to.len() && from.iter().zip(to.iter()).all(|(a, b)| a == b)) {
+        return Err(EncryptedFileError::InvalidPath);
+    }
+    let mut parent = DirectoryContentPath::default();
+    for name in to.iter().take(to.len() - 1) {
+        parent.push(name).expect("Names are not empty");
+        if content.get_file(&parent).is_some() {
+            return Err(EncryptedFileError::InvalidPath);
+        }
+    }
+    Ok(())
+}
+
+/// Replaces `path` with a copy of the archive where everything under `from` is moved to `to`
+/// Encrypted content, key, digest and signature are copied as they are, nothing is decrypted
+pub fn move_entry(path: &Path, from: &DirectoryContentPath, to: &DirectoryContentPath) -> EncryptedFileResult<()> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let mut output = PendingFile::create(Box::from(path))?;
+    let prefix = format!("{CONTENT_DIR}{from}/");
+    let mut writer = ZipWriter::new(output.file());
+    for i in 0..zip.len() {
+        let file = zip.by_index_raw(i)?;
+        match file.name().strip_prefix(&prefix).map(|rest| format!("{CONTENT_DIR}{to}/{rest}")) {
+            Some(name) => writer.raw_copy_file_rename(file, name)?,
+            None => writer.raw_copy_file(file)?,
+        }
+    }
+    writer.finish()?;
+    drop(writer);
+    output.persist()?;
+    Ok(())
+}
+
+/// What to do with an entry of merged archive whose path is already taken
+#[derive(Clone, Copy)]
+pub enum Conflict {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+/// What happened to a single entry of merged archive
+pub enum Merged {
+    Added,
+    Overwritten,
+    Renamed(DirectoryContentPath),
+    Skipped,
+}
+
+fn prefixes(path: &DirectoryContentPath) -> impl Iterator<Item = DirectoryContentPath> + '_ {
+    (1..path.len()).map(|len| {
+        let mut prefix = DirectoryContentPath::default();
+        for name in path.iter().take(len) {
+            prefix.push(name).expect("Names are not empty");
+        }
+        prefix
+    })
+}
+
+/// Whether any directory on the way to `path` is a file, in which case `path` can never be added
+fn blocked(content: &DirectoryContent, planned: &HashSet<String>, path: &DirectoryContentPath) -> bool {
+    prefixes(path).any(|prefix| content.get_file(&prefix).is_some() || planned.contains(&prefix.to_string()))
+}
+
+fn is_free(content: &DirectoryContent, planned: &HashSet<String>, path: &DirectoryContentPath) -> bool {
+    let dir = format!("{path}/");
+    content.get_file(path).is_none() && content.get_dir(path).is_none() && !blocked(content, planned, path)
+        && !planned.contains(&path.to_string()) && !planned.iter().any(|other| other.starts_with(&dir))
+}
+
+/// First of `name (1).ext`, `name (2).ext`, ... which isn't taken
+fn free_path(content: &DirectoryContent, planned: &HashSet<String>, path: &DirectoryContentPath) -> DirectoryContentPath {
+    let name = path.file_name().unwrap_or("content");
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{extension}")),
+        _ => (name, String::new()),
+    };
+    let mut parent = path.clone();
+    parent.pop();
+    (1..).map(|i| {
+        let mut candidate = parent.clone();
+        candidate.push(&format!("{stem} ({i}){extension}")).expect("Names are not empty");
+        candidate
+    }).find(|candidate| is_free(content, planned, candidate)).expect("There are finitely many taken paths")
+}
+
+/// Replaces `path` with the archive extended by all entries of `other`, they're copied as ciphertext without re-encryption
+/// `content` is content of the archive at `path`
+pub fn merge(path: &Path, content: &DirectoryContent, other: &mut EncryptedFile, other_path: &Path, conflict: Conflict) -> EncryptedFileResult<Vec<(DirectoryContentPath, Merged)>> {
+    let mut planned = HashSet::new();
+    let mut renames = HashMap::new();
+    let mut overwritten = Vec::new();
+    let mut report = Vec::new();
+    for entry in entry_files(other.get_directory_content()?, &DirectoryContentPath::default()).unwrap_or_default() {
+        // Entries with `.` or `..` elements are refused like in `add_file`
+        let merged = match conflict {
+            _ if check_entry(&entry).is_err() => Merged::Skipped,
+            _ if is_free(content, &planned, &entry) => Merged::Added,
+            _ if blocked(content, &planned, &entry) => Merged::Skipped,
+            Conflict::Overwrite if content.get_file(&entry).is_some() => Merged::Overwritten,
+            Conflict::Rename => Merged::Renamed(free_path(content, &planned, &entry)),
+            _ => Merged::Skipped,
+        };
+        let target = match &merged {
+            Merged::Added => Some(entry.to_string()),
+            Merged::Overwritten => {
+                overwritten.push(format!("{CONTENT_DIR}{entry}/"));
+                Some(entry.to_string())
+            }
+            Merged::Renamed(to) => Some(to.to_string()),
+            Merged::Skipped => None,
+        };
+        if let Some(target) = target {
+            planned.insert(target.clone());
+            renames.insert(entry.to_string(), target);
+        }
+        report.push((entry, merged));
+    }
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let mut other_zip = ZipArchive::new(File::open(other_path)?)?;
+    let mut output = PendingFile::create(Box::from(path))?;
+    let mut writer = ZipWriter::new(output.file());
+    for i in 0..zip.len() {
+        let file = zip.by_index_raw(i)?;
+        if !overwritten.iter().any(|prefix| file.name().starts_with(prefix)) {
+            writer.raw_copy_file(file)?;
+        }
+    }
+    for i in 0..other_zip.len() {
+        let file = other_zip.by_index_raw(i)?;
+        let target = file.name().strip_prefix(CONTENT_DIR)
+            .and_then(|name| name.rsplit_once('/'))
+            .and_then(|(entry, part)| renames.get(entry).map(|target| format!("{CONTENT_DIR}{target}/{part}")));
+        if let Some(name) = target {
+            writer.raw_copy_file_rename(file, name)?;
+        }
+    }
+    writer.finish()?;
+    drop(writer);
+    output.persist()?;
+    Ok(report)
+}
+
+// Parts of an entry written by the_lock_lib, see `CONTENT_DIR`
+const KEY_PART: &str = "key";
+const DIGEST_PART: &str = "digest";
+const SIGNATURE_PART: &str = "signature";
+
+/// Indexes of zip files grouped by entry they belong to
+fn entry_parts(zip: &mut ZipArchive<File>) -> ZipResult<HashMap<String, Vec<usize>>> {
+    let mut ans: HashMap<String, Vec<usize>> = HashMap::new();
+    for i in 0..zip.len() {
+        let file = zip.by_index_raw(i)?;
+        if let Some((entry, _)) = file.name().strip_prefix(CONTENT_DIR).and_then(|name| name.rsplit_once('/')) {
+            ans.entry(entry.to_owned()).or_default().push(i);
+        }
+    }
+    Ok(ans)
+}
+
+/// Keys used to move entries to a new recipient
+pub struct Rekey<'a> {
+    /// Key of the current recipient
+    pub private_key: &'a PrivateKey,
+    /// Key of the new recipient
+    pub public_key: &'a PublicKey,
+    /// Key used to re-sign signed entries, they keep original signatures without it
+    pub signer: Option<&'a RsaPrivateKey>,
+}
+
+/// Key and signature which replace the original ones
+fn rekeyed_parts(zip: &mut ZipArchive<File>, encrypted_file: &EncryptedFile, entry: &DirectoryContentPath, keys: &Rekey) -> EncryptedFileResult<(Vec<u8>, Option<Vec<u8>>)> {
+    let Rekey { private_key, public_key, signer } = *keys;
+    let mut key = Vec::new();
+    zip.by_name(&format!("{CONTENT_DIR}{entry}/{KEY_PART}"))?.read_to_end(&mut key)?;
+    let key = public_key.encrypt_symmetric_key(&private_key.decrypt_symmetric_key(&key)?)?;
+    let signed = encrypted_file.get_directory_content_soft().and_then(|content| content.get_file(entry)).is_some_and(|file| file.is_signed());
+    let signature = match signer {
+        Some(signer) if signed => {
+            // Digest is signed, so it has to match the content first
+            if !encrypted_file.decrypt_file(entry, io::sink(), private_key)? {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "digest is incorrect, entry can't be re-signed").into());
+            }
+            let mut digest = [0; 64];
+            zip.by_name(&format!("{CONTENT_DIR}{entry}/{DIGEST_PART}"))?.read_exact(&mut digest)?;
+            Some(signer.sign_with_rng(&mut OsRng, Pss::new::<Sha512>(), &digest)?)
+        }
+        _ => None,
+    };
+    Ok((key, signature))
+}
+
+/// Writes `entries` to a new archive in `output` with their symmetric keys rewrapped for the new recipient, content isn't re-encrypted
+/// Entries which fail are left out and reported to `callback`, which stops rekeying by returning `false`
+pub fn rekey<C>(path: &Path, encrypted_file: &EncryptedFile, entries: &[DirectoryContentPath], output: &mut File, keys: &Rekey, mut callback: C) -> EncryptedFileResult<()>
+    where C: FnMut(&DirectoryContentPath, &EncryptedFileResult<()>) -> bool {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let parts = entry_parts(&mut zip)?;
+    let options = *encrypted_file.zip_file_options();
+    let mut writer = ZipWriter::new(output);
+    for entry in entries {
+        let result = rekeyed_parts(&mut zip, encrypted_file, entry, keys).and_then(|(key, signature)| {
+            for &i in parts.get(&entry.to_string()).into_iter().flatten() {
+                let file = zip.by_index_raw(i)?;
+                let part = file.name().rsplit_once('/').map_or("", |(_, part)| part);
+                if part != KEY_PART && !(part == SIGNATURE_PART && signature.is_some()) {
+                    writer.raw_copy_file(file)?;
+                }
+            }
+            writer.start_file(format!("{CONTENT_DIR}{entry}/{KEY_PART}"), options)?;
+            writer.write_all(&key)?;
+            if let Some(signature) = signature {
+                writer.start_file(format!("{CONTENT_DIR}{entry}/{SIGNATURE_PART}"), options)?;
+                writer.write_all(&signature)?;
+            }
+            Ok(())
+        });
+        if !callback(entry, &result) {
+            break;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Adds `src` to the archive, signing it if `private_key` is given
+/// Entries with `.` or `..` elements are refused, they couldn't be decrypted back
+pub fn add_file(encrypted_file: &mut EncryptedFile, src: File, dst: &DirectoryContentPath, public_key: &PublicKey, private_key: Option<&RsaPrivateKey>) -> EncryptedFileResult<()> {
+    check_entry(dst)?;
+    let metadata = src.metadata()?;
+    encrypted_file.set_zip_file_options(encrypted_file.zip_file_options().large_file(metadata.len() >= 4*1024*1024*1024));
+    match private_key {
+        Some(private_key) => encrypted_file.add_file_and_sign(src, dst, public_key, private_key),
+        None => encrypted_file.add_file(src, dst, public_key),
+    }
+}
+
+/// Fails with `InvalidPath` if `dst` has `.` or `..` elements
+#[inline]
+pub fn check_entry(dst: &DirectoryContentPath) -> EncryptedFileResult<()> {
+    match is_safe_entry(dst) {
+        true => Ok(()),
+        false => Err(EncryptedFileError::InvalidPath),
+    }
+}
+
+/// Digest of every entry which has one, it's SHA-512 of the plaintext, so reading it doesn't need any key
+pub fn stored_digests(path: &Path) -> ZipResult<HashMap<String, [u8; 64]>> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let mut ans = HashMap::new();
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let Some((entry, DIGEST_PART)) = file.name().strip_prefix(CONTENT_DIR).and_then(|name| name.rsplit_once('/')) else {
+            continue;
+        };
+        let entry = entry.to_owned();
+        let mut digest = [0; 64];
+        if file.read_exact(&mut digest).is_ok() {
+            ans.insert(entry, digest);
+        }
+    }
+    Ok(ans)
+}
+
+/// Whether content of `src` differs from the one whose stored digest is `digest`
+pub fn differs_from_digest(src: &Path, digest: &[u8; 64]) -> io::Result<bool> {
+    let mut source = Sha512::new();
+    io::copy(&mut File::open(src)?, &mut source)?;
+    Ok(source.finalize().as_slice() != digest)
+}
+
+/// Whether content of `src` differs from the archived entry `dst`, which is decrypted to compare them
+pub fn content_changed(encrypted_file: &EncryptedFile, src: &Path, dst: &DirectoryContentPath, private_key: &PrivateKey) -> EncryptedFileResult<bool> {
+    let mut source = Sha256::new();
+    io::copy(&mut File::open(src)?, &mut source)?;
+    let mut archived = Sha256::new();
+    encrypted_file.decrypt_file(dst, &mut archived, private_key)?;
+    Ok(source.finalize() != archived.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_to_parent_dir_is_rejected() {
+        let content = DirectoryContent::new();
+        let from = DirectoryContentPath::from("file.txt");
+        for to in ["../x", "./x", "dir/../x", "dir/."] {
+            assert!(matches!(check_move(&content, &from, &DirectoryContentPath::from(to)), Err(EncryptedFileError::InvalidPath)), "{to}");
+        }
+        assert!(matches!(check_move(&content, &from, &DirectoryContentPath::from("dir/x")), Err(EncryptedFileError::FileDoesNotExist)));
+    }
+}
@@ -0,0 +1,107 @@
+use std::{collections::BTreeMap, fs::File, path::Path};
+
+use the_lock_lib::{CompressionMethod, directory_content::DirectoryContent};
+use zip::{ZipArchive, result::ZipResult};
+
+use crate::extraction::entry_files;
+use crate::utils::{print_table, error_font};
+
+// Layout of the archive written by the_lock_lib: `content/<entry path>/<part>`
+pub const CONTENT_DIR: &str = "content/";
+const CONTENT_PART: &str = "file";
+
+/// Sizes of a single entry summed over all of its parts (content, key, digest and signature)
+#[derive(Default)]
+struct EntrySizes {
+    compressed: u64,
+    uncompressed: u64,
+    method: Option<CompressionMethod>,
+}
+
+/// Overview of an archive built from its zip central directory and its content
+pub struct ArchiveInfo {
+    entries: BTreeMap<String, EntrySizes>,
+    signed: usize,
+    missing_digest: usize,
+    missing_key: Vec<String>,
+}
+
+impl ArchiveInfo {
+    /// Reads sizes straight from the zip, nothing is decrypted
+    pub fn read(path: &Path, content: &DirectoryContent) -> ZipResult<Self> {
+        let mut zip = ZipArchive::new(File::open(path)?)?;
+        let mut entries: BTreeMap<String, EntrySizes> = BTreeMap::new();
+        for i in 0..zip.len() {
+            let file = zip.by_index_raw(i)?;
+            let Some((entry, part)) = file.name().strip_prefix(CONTENT_DIR).and_then(|name| name.rsplit_once('/')) else {
+                continue;
+            };
+            let sizes = entries.entry(entry.to_owned()).or_default();
+            sizes.compressed += file.compressed_size();
+            sizes.uncompressed += file.size();
+            if part == CONTENT_PART {
+                sizes.method = Some(file.compression());
+            }
+        }
+        let (mut signed, mut missing_digest, mut missing_key) = (0, 0, Vec::new());
+        for path in entry_files(content, &Default::default()).unwrap_or_default() {
+            let Some(file) = content.get_file(&path) else {
+                continue;
+            };
+            signed += file.is_signed() as usize;
+            missing_digest += !file.has_digest() as usize;
+            if !file.has_key() {
+                missing_key.push(path.to_string());
+            }
+            entries.entry(path.to_string()).or_default();
+        }
+        Ok(Self { entries, signed, missing_digest, missing_key })
+    }
+
+    /// `zip_options` describes options used for newly added entries, as compression level isn't stored in the archive
+    pub fn print(&self, zip_options: &str) {
+        let (compressed, uncompressed) = self.entries.values().fold((0, 0), |(compressed, uncompressed), sizes| (compressed + sizes.compressed, uncompressed + sizes.uncompressed));
+        println!("Entries: {}", self.entries.len());
+        println!("Signed: {}, missing digest: {}, missing key: {}", self.signed, self.missing_digest, self.missing_key.len());
+        println!("Total size: {} compressed, {} uncompressed ({})", human_size(compressed), human_size(uncompressed), ratio(compressed, uncompressed));
+        println!("Zip file options for new entries: {zip_options}");
+        println!();
+        let rows: Vec<([String; 5], bool)> = self.entries.iter().map(|(path, sizes)| ([
+            path.clone(),
+            sizes.method.map_or("-".to_owned(), |method| method.to_string()),
+            human_size(sizes.compressed),
+            human_size(sizes.uncompressed),
+            ratio(sizes.compressed, sizes.uncompressed),
+        ], self.missing_key.contains(path))).collect();
+        print_table(["Entry", "Method", "Compressed", "Uncompressed", "Ratio"], &rows);
+        if !self.missing_key.is_empty() {
+            println!();
+            println!("{}", error_font("Entries without a key, they can never be decrypted:"));
+            for path in &self.missing_key {
+                println!("  {path}");
+            }
+        }
+    }
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
+
+#[inline]
+fn ratio(compressed: u64, uncompressed: u64) -> String {
+    match uncompressed {
+        0 => "-".to_owned(),
+        _ => format!("{:.0}%", compressed as f64 * 100.0 / uncompressed as f64),
+    }
+}
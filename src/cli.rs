@@ -0,0 +1,821 @@
+use std::{fmt::Display, path::{Path, PathBuf}, fs::File, io, process::ExitCode};
+
+use clap::{Parser, Subcommand, Args, ValueEnum};
+use indicatif::ProgressBar;
+use the_lock_lib::{EncryptedFile, directory_content::{DirectoryContent, DirectoryContentPath}, asymetric_key::{PrivateKey, PublicKey, MIN_RSA_KEY_SIZE}, rsa::RsaPrivateKey, signers_list::SignersList, error::EncryptedFileError};
+
+use crate::archive_edit::{add_file, check_entry};
+use crate::path_filter::{PathFilter, EntryGlobs};
+use crate::report::{Output, Record, error_kind};
+use crate::extraction::{Verification, Outcome, entry_files, file_target, entry_targets, directory_targets, decrypt_atomically, decrypt_entry, saved_to, print_summary};
+use crate::encrypted_file_handler::{decrypted_file_output, decrypted_file_and_verify_output, decrypted_file_and_find_signer_output};
+use crate::key_metadata::{self, KeyFile, KeyMetadata};
+use crate::utils::{Fingerprint, read_with_password, read_key_with_password, read_with_password_or, save_to, save_private_to, create_encrypted_file_at, println_error, green_font, error_font};
+
+#[derive(Parser)]
+#[command(version, about = "Encrypt files and pack them into an archive")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Output format of subcommands
+    #[arg(long, global = true, value_enum, default_value_t = Output::Text)]
+    pub output: Output,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Encrypt files and directories into an archive
+    Encrypt(EncryptArgs),
+    /// Decrypt files and directories from an archive
+    Decrypt(DecryptArgs),
+    /// Check digests and signatures without writing decrypted files anywhere
+    Verify(VerifyArgs),
+    /// Create and convert keys
+    Key {
+        #[command(subcommand)]
+        command: KeyCommand,
+    },
+    /// Manage signers lists
+    Signers {
+        #[command(subcommand)]
+        command: SignersCommand,
+    },
+}
+
+#[derive(Args)]
+pub struct EncryptArgs {
+    /// Archive to add files to, it's created if it doesn't exist
+    #[arg(long)]
+    archive: PathBuf,
+    /// Public key (or private key) of the recipient
+    #[arg(long)]
+    public_key: PathBuf,
+    /// Directory inside of the archive
+    #[arg(long, default_value = "")]
+    dst: String,
+    /// Private key (or private RSA key) used to sign added files
+    #[arg(long)]
+    sign_with: Option<PathBuf>,
+    /// File containing password for password protected keys
+    #[arg(long)]
+    password_file: Option<PathBuf>,
+    /// Add only files of directories matching one of these glob patterns
+    #[arg(long)]
+    include: Vec<String>,
+    /// Skip files and directories matching these glob patterns
+    #[arg(long)]
+    exclude: Vec<String>,
+    /// Honor .gitignore and .lockignore files inside of directories
+    #[arg(long)]
+    ignore_files: bool,
+    /// List files which would be added without changing the archive
+    #[arg(long)]
+    dry_run: bool,
+    /// Files and directories to encrypt
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct DecryptArgs {
+    /// Archive to decrypt files from
+    #[arg(long)]
+    archive: PathBuf,
+    /// Private key of the recipient
+    #[arg(long)]
+    private_key: PathBuf,
+    /// Verify signatures with this public RSA key (or public key)
+    #[arg(long, conflicts_with = "signers")]
+    verify_with: Option<PathBuf>,
+    /// Find signers of decrypted files in this signers list
+    #[arg(long)]
+    signers: Option<PathBuf>,
+    /// File containing password for password protected keys
+    #[arg(long)]
+    password_file: Option<PathBuf>,
+    /// Output directory, it's created if it doesn't exist
+    #[arg(long, default_value = ".")]
+    out: PathBuf,
+    /// Decrypt every entry matching one of these glob patterns, keeping the archive's tree in the output directory
+    #[arg(long)]
+    glob: Vec<String>,
+    /// Files and directories inside of the archive
+    #[arg(required_unless_present = "glob")]
+    entries: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Archive to verify
+    #[arg(long)]
+    archive: PathBuf,
+    /// Private key of the recipient
+    #[arg(long)]
+    private_key: PathBuf,
+    /// Verify signatures with this public RSA key (or public key)
+    #[arg(long, conflicts_with = "signers")]
+    verify_with: Option<PathBuf>,
+    /// Find signers of files in this signers list
+    #[arg(long)]
+    signers: Option<PathBuf>,
+    /// File containing password for password protected keys
+    #[arg(long)]
+    password_file: Option<PathBuf>,
+    /// Files and directories inside of the archive, whole archive if none is given
+    entries: Vec<String>,
+}
+
+#[derive(Subcommand)]
+pub enum KeyCommand {
+    /// Create new private key
+    New(KeyNewArgs),
+    /// Derive public or RSA key from a private key
+    Derive(KeyDeriveArgs),
+}
+
+#[derive(Args)]
+pub struct KeyNewArgs {
+    /// Size of the RSA key
+    #[arg(long, default_value_t = MIN_RSA_KEY_SIZE)]
+    bits: usize,
+    /// Where to save the key
+    #[arg(long)]
+    out: PathBuf,
+    /// Protect saved key with password from this file
+    #[arg(long)]
+    password_file: Option<PathBuf>,
+    /// Overwrite output if it already exists
+    #[arg(long)]
+    force: bool,
+    /// Label saved with the key
+    #[arg(long, default_value = "")]
+    label: String,
+    /// Email of the key owner
+    #[arg(long, default_value = "")]
+    owner: String,
+    /// Date when the key expires (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_expiry)]
+    expires: Option<i64>,
+    /// Comment saved with the key
+    #[arg(long, default_value = "")]
+    comment: String,
+}
+
+fn parse_expiry(date: &str) -> Result<i64, String> {
+    key_metadata::parse_date(date).ok_or_else(|| "expected date in YYYY-MM-DD format".to_owned())
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DerivedKey {
+    Public,
+    RsaPrivate,
+    RsaPublic,
+}
+
+#[derive(Args)]
+pub struct KeyDeriveArgs {
+    /// Private key to derive from
+    #[arg(long)]
+    from: PathBuf,
+    /// Kind of the derived key
+    #[arg(long)]
+    to: DerivedKey,
+    /// Where to save derived key
+    #[arg(long)]
+    out: PathBuf,
+    /// File containing password of the source key
+    #[arg(long)]
+    password_file: Option<PathBuf>,
+    /// Protect derived key with password from this file
+    #[arg(long)]
+    out_password_file: Option<PathBuf>,
+    /// Overwrite output if it already exists
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Subcommand)]
+pub enum SignersCommand {
+    /// Create new, empty signers list
+    Init {
+        /// Directory of the signers list, it's created if it doesn't exist
+        dir: PathBuf,
+    },
+    /// Add signer to the signers list
+    Add {
+        /// Directory of the signers list
+        dir: PathBuf,
+        /// Name of the new signer
+        name: String,
+        /// Public RSA key (or public key) of the signer
+        key_file: PathBuf,
+        /// File containing password for password protected key
+        #[arg(long)]
+        password_file: Option<PathBuf>,
+    },
+    /// Remove signer from the signers list
+    Rm {
+        /// Directory of the signers list
+        dir: PathBuf,
+        /// Name of the signer
+        name: String,
+    },
+    /// List signers
+    Ls {
+        /// Directory of the signers list
+        dir: PathBuf,
+    },
+    /// Save signer's public RSA key to a file
+    Export {
+        /// Directory of the signers list
+        dir: PathBuf,
+        /// Name of the signer
+        name: String,
+        /// Where to save the key
+        #[arg(long)]
+        out: PathBuf,
+        /// Overwrite output if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl Command {
+    /// Name of the operation in machine readable output
+    fn operation(&self) -> &'static str {
+        match self {
+            Command::Encrypt(_) => "encrypt",
+            Command::Decrypt(_) => "decrypt",
+            Command::Verify(_) => "verify",
+            Command::Key { command: KeyCommand::New(_) } => "key new",
+            Command::Key { command: KeyCommand::Derive(_) } => "key derive",
+            Command::Signers { command: SignersCommand::Init { .. } } => "signers init",
+            Command::Signers { command: SignersCommand::Add { .. } } => "signers add",
+            Command::Signers { command: SignersCommand::Rm { .. } } => "signers rm",
+            Command::Signers { command: SignersCommand::Ls { .. } } => "signers ls",
+            Command::Signers { command: SignersCommand::Export { .. } } => "signers export",
+        }
+    }
+}
+
+pub fn run(command: Command, output: Output) -> ExitCode {
+    let operation = command.operation();
+    let result = match command {
+        Command::Encrypt(args) => encrypt(args, output),
+        Command::Decrypt(args) => decrypt(args, output),
+        Command::Verify(args) => verify(args, output),
+        Command::Key { command: KeyCommand::New(args) } => key_new(args, output),
+        Command::Key { command: KeyCommand::Derive(args) } => key_derive(args, output),
+        Command::Signers { command } => signers(command, output),
+    };
+    result.unwrap_or_else(|failure| failure.report(operation, output))
+}
+
+/// Error which stops a whole command, its reason is printed as soon as it happens
+struct Failure {
+    path: String,
+    kind: &'static str,
+    message: String,
+}
+
+impl Failure {
+    fn new(path: impl Display, kind: &'static str, message: String) -> Self {
+        println_error(&message);
+        Self::printed(path, kind, message)
+    }
+
+    /// Failure of a helper which has already printed why it failed
+    fn printed(path: impl Display, kind: &'static str, message: impl ToString) -> Self {
+        Self { path: path.to_string(), kind, message: message.to_string() }
+    }
+
+    /// In JSON mode it's reported as a record too
+    fn report(self, operation: &'static str, output: Output) -> ExitCode {
+        if output == Output::Json {
+            Record::new(operation, self.path).failed(self.kind, self.message).emit();
+        }
+        ExitCode::FAILURE
+    }
+}
+
+fn read_password(path: Option<&Path>) -> Result<Option<Vec<u8>>, Failure> {
+    match path {
+        Some(path) => match std::fs::read(path) {
+            Ok(mut password) => {
+                while let Some(b'\n' | b'\r') = password.last() {
+                    password.pop();
+                }
+                Ok(Some(password))
+            }
+            Err(err) => Err(Failure::new(path.display(), "IOError", format!("Couldn't read password file {} - {err}", path.display()))),
+        },
+        None => Ok(None),
+    }
+}
+
+fn check_output(path: &Path, force: bool) -> Result<(), Failure> {
+    match path.exists() && !force {
+        true => Err(Failure::new(path.display(), "FileAlreadyExists", format!("{} already exists, use --force to overwrite it", path.display()))),
+        false => Ok(()),
+    }
+}
+
+/// `read_with_password` and friends print why they failed
+#[inline]
+fn key_failure(path: &Path) -> Failure {
+    Failure::printed(path.display(), "KeyError", format!("Couldn't read key {}", path.display()))
+}
+
+/// `save_to` prints why it failed
+#[inline]
+fn save_failure(path: &Path) -> Failure {
+    Failure::printed(path.display(), "IOError", format!("Couldn't save {}", path.display()))
+}
+
+#[inline]
+fn progress_bar(output: Output) -> ProgressBar {
+    match output {
+        Output::Text => ProgressBar::new(0),
+        Output::Json => ProgressBar::hidden(),
+    }
+}
+
+fn report(output: Output, record: Record, text: &str) {
+    match output {
+        Output::Text => println!("{text}"),
+        Output::Json => record.emit(),
+    }
+}
+
+#[inline]
+fn exit_code(success: bool) -> ExitCode {
+    match success {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
+
+fn encrypt(args: EncryptArgs, output: Output) -> Result<ExitCode, Failure> {
+    let filter = PathFilter::new(args.include.iter().map(String::as_str), args.exclude.iter().map(String::as_str), args.ignore_files);
+    if check_entry(&DirectoryContentPath::from(args.dst.as_str())).is_err() {
+        return Err(Failure::new(&args.dst, "InvalidPath", format!("Invalid destination {:?} - it can't contain . or .. elements", args.dst)));
+    }
+    if args.dry_run {
+        return Ok(encrypt_dry_run(&args, &filter, output));
+    }
+    let password = read_password(args.password_file.as_deref())?;
+    let public_key = read_with_password_or(&args.public_key, password.as_deref(), |key: PrivateKey| key.get_public_key()).ok_or_else(|| key_failure(&args.public_key))?;
+    let private_key = match &args.sign_with {
+        Some(path) => {
+            let mut key = read_with_password_or::<RsaPrivateKey, _, _>(path, password.as_deref(), |key: PrivateKey| key.get_rsa_private_key().to_owned()).ok_or_else(|| key_failure(path))?;
+            key.precompute().map_err(|err| Failure::new(path.display(), "RSAError", format!("RSA precomputions failed - {}", err)))?;
+            Some(key)
+        }
+        None => None,
+    };
+    let encrypted_file = match args.archive.exists() {
+        true => EncryptedFile::new(&args.archive),
+        false => create_encrypted_file_at(&args.archive),
+    };
+    let mut encrypted_file = encrypted_file.map_err(|err| Failure::new(args.archive.display(), "IOError", format!("Couldn't open {} - {err}", args.archive.display())))?;
+    if let Err(err) = encrypted_file.get_directory_content() {
+        return Err(Failure::new(args.archive.display(), error_kind(&err), format!("Couldn't read archive content - {err}")));
+    }
+    let mut success = true;
+    for src in &args.paths {
+        if src.is_dir() {
+            success &= encrypt_directory(&mut encrypted_file, src, DirectoryContentPath::from(args.dst.as_str()), &filter, &public_key, private_key.as_ref(), output);
+        }
+        else {
+            success &= encrypt_file(&mut encrypted_file, src, DirectoryContentPath::from(args.dst.as_str()), &public_key, private_key.as_ref(), output);
+        }
+    }
+    Ok(exit_code(success))
+}
+
+fn encrypt_file(encrypted_file: &mut EncryptedFile, src: &Path, mut dst_path: DirectoryContentPath, public_key: &PublicKey, private_key: Option<&RsaPrivateKey>, output: Output) -> bool {
+    let fail = |kind: &'static str, msg: String| {
+        match output {
+            Output::Text => println_error(&msg),
+            Output::Json => Record::new("encrypt", src.display()).failed(kind, msg).emit(),
+        }
+        false
+    };
+    let file = match File::open(src) {
+        Ok(file) => file,
+        Err(err) => return fail("IOError", format!("Couldn't open {} - {err}", src.display())),
+    };
+    if let Err(err) = dst_path.push(&src.file_name().unwrap_or_default().to_string_lossy()) {
+        return fail("InvalidPath", format!("Invalid destination for {} - {err}", src.display()));
+    }
+    let result = add_file(encrypted_file, file, &dst_path, public_key, private_key);
+    match (output, &result) {
+        (Output::Text, Ok(())) => println!("{} saved to dst {}", src.display(), dst_path),
+        (Output::Text, Err(err)) => println_error(&format!("Couldn't save {} to {} - {}", src.display(), dst_path, err)),
+        (Output::Json, _) => Record::new("encrypt", src.display()).destination(&dst_path).encrypted(&result).emit(),
+    }
+    result.is_ok()
+}
+
+/// Lists what `encrypt` would add, nothing is read besides the source directories
+fn encrypt_dry_run(args: &EncryptArgs, filter: &PathFilter, output: Output) -> ExitCode {
+    let mut success = true;
+    for src in &args.paths {
+        let mut dst = DirectoryContentPath::from(args.dst.as_str());
+        let files = match src.is_dir() {
+            true => filter.files(src, dst),
+            false => dst.push(&src.file_name().unwrap_or_default().to_string_lossy()).map(|()| vec![(src.clone(), dst)]).map_err(|_| EncryptedFileError::InvalidPath),
+        };
+        match (output, files) {
+            (Output::Text, Ok(files)) => for (src, dst) in files {
+                match check_entry(&dst) {
+                    Ok(()) => println!("{} -> {}", src.display(), dst),
+                    Err(err) => {
+                        println_error(&format!("{} -> {} - {err}", src.display(), dst));
+                        success = false;
+                    }
+                }
+            },
+            (Output::Json, Ok(files)) => for (src, dst) in files {
+                let result = check_entry(&dst);
+                success &= result.is_ok();
+                Record::new("dry-run", src.display()).destination(dst).encrypted(&result).emit();
+            },
+            (Output::Text, Err(err)) => {
+                println_error(&format!("Couldn't read {} - {err}", src.display()));
+                success = false;
+            }
+            (Output::Json, Err(err)) => {
+                Record::new("dry-run", src.display()).encrypted(&Err(err)).emit();
+                success = false;
+            }
+        }
+    }
+    exit_code(success)
+}
+
+fn encrypt_directory(encrypted_file: &mut EncryptedFile, src: &Path, dst_path: DirectoryContentPath, filter: &PathFilter, public_key: &PublicKey, private_key: Option<&RsaPrivateKey>, output: Output) -> bool {
+    let files = match filter.files(src, dst_path) {
+        Ok(files) => files,
+        Err(err) => {
+            match output {
+                Output::Text => println_error(&format!("Error occured when trying to add {} - {err}", src.display())),
+                Output::Json => Record::new("encrypt", src.display()).encrypted(&Err(err)).emit(),
+            }
+            return false;
+        }
+    };
+    let bar = progress_bar(output);
+    bar.set_length(files.len() as u64);
+    let mut success = true;
+    for (src, dst) in &files {
+        let result = File::open(src).map_err(EncryptedFileError::from).and_then(|file| add_file(encrypted_file, file, dst, public_key, private_key));
+        match (output, &result) {
+            (Output::Text, Ok(())) => bar.suspend(|| println!("{:?} saved to dst {}", src, dst)),
+            (Output::Text, Err(err)) => bar.suspend(|| println_error(&format!("Couldn't save {:?} to {} - {}", src, dst, err))),
+            (Output::Json, _) => Record::new("encrypt", src.display()).destination(dst).encrypted(&result).emit(),
+        }
+        success &= result.is_ok();
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    success
+}
+
+fn read_private_key(path: &Path, password: Option<&[u8]>) -> Result<PrivateKey, Failure> {
+    let mut key = read_with_password::<PrivateKey>(path, password).ok_or_else(|| key_failure(path))?;
+    key.rsa_precomput().map_err(|err| Failure::new(path.display(), "RSAError", format!("RSA precomputions failed - {}", err)))?;
+    Ok(key)
+}
+
+fn read_verification(verify_with: Option<&Path>, signers: Option<&Path>, password: Option<&[u8]>) -> Result<Verification, Failure> {
+    match (verify_with, signers) {
+        (Some(path), _) => read_with_password_or(path, password, |key: PublicKey| key.get_rsa_public_key().to_owned()).map(Verification::Verify).ok_or_else(|| key_failure(path)),
+        (None, Some(path)) => open_signers_list(path).map(Verification::FindSigner),
+        (None, None) => Ok(Verification::None),
+    }
+}
+
+fn open_archive(path: &Path) -> Result<(EncryptedFile, DirectoryContent), Failure> {
+    if !path.is_file() {
+        return Err(Failure::new(path.display(), "FileDoesNotExist", format!("{} is not a file", path.display())));
+    }
+    let mut encrypted_file = EncryptedFile::new(path).map_err(|err| Failure::new(path.display(), "IOError", format!("Couldn't open {} - {err}", path.display())))?;
+    let content = match encrypted_file.get_directory_content() {
+        Ok(content) => content.clone(),
+        Err(err) => return Err(Failure::new(path.display(), error_kind(&err), format!("Couldn't read archive content - {err}"))),
+    };
+    Ok((encrypted_file, content))
+}
+
+fn decrypt(args: DecryptArgs, output: Output) -> Result<ExitCode, Failure> {
+    let password = read_password(args.password_file.as_deref())?;
+    let private_key = read_private_key(&args.private_key, password.as_deref())?;
+    let verification = read_verification(args.verify_with.as_deref(), args.signers.as_deref(), password.as_deref())?;
+    let (encrypted_file, content) = open_archive(&args.archive)?;
+    if let Err(err) = std::fs::create_dir_all(&args.out) {
+        return Err(Failure::new(args.out.display(), "IOError", format!("Couldn't create {} - {err}", args.out.display())));
+    }
+    let mut success = true;
+    for entry in &args.entries {
+        let src = DirectoryContentPath::from(entry.as_str());
+        if content.get_file(&src).is_some() {
+            success &= decrypt_file(&encrypted_file, &src, &args.out, &private_key, &verification, output);
+        }
+        else if content.get_dir(&src).is_some() {
+            success &= decrypt_directory(&encrypted_file, src, &args.out, &private_key, &verification, output);
+        }
+        else {
+            match output {
+                Output::Text => println_error(&format!("{entry} doesn't exist in the archive")),
+                Output::Json => Record::new("decrypt", entry).failed("FileDoesNotExist", "Entry doesn't exist in the archive").emit(),
+            }
+            success = false;
+        }
+    }
+    if !args.glob.is_empty() {
+        let globs = EntryGlobs::new(args.glob.iter().map(String::as_str));
+        let matching: Vec<_> = entry_files(&content, &DirectoryContentPath::default()).unwrap_or_default().into_iter().filter(|entry| globs.matches(&entry.to_string())).collect();
+        if matching.is_empty() {
+            match output {
+                Output::Text => println_error("No entries match the glob patterns"),
+                Output::Json => Record::new("decrypt", args.glob.join(",")).failed("FileDoesNotExist", "No entries match the glob patterns").emit(),
+            }
+            success = false;
+        }
+        match entry_targets(matching, &args.out) {
+            Ok(targets) => success &= decrypt_targets(&encrypted_file, targets, &private_key, &verification, output),
+            Err(err) => {
+                match output {
+                    Output::Text => println_error(&format!("Nothing has been decrypted - {err}")),
+                    Output::Json => Record::new("decrypt", args.glob.join(",")).failed(err.kind(), err).emit(),
+                }
+                success = false;
+            }
+        }
+    }
+    Ok(exit_code(success))
+}
+
+fn verify(args: VerifyArgs, output: Output) -> Result<ExitCode, Failure> {
+    let password = read_password(args.password_file.as_deref())?;
+    let private_key = read_private_key(&args.private_key, password.as_deref())?;
+    let verification = read_verification(args.verify_with.as_deref(), args.signers.as_deref(), password.as_deref())?;
+    let (encrypted_file, content) = open_archive(&args.archive)?;
+    let entries = match args.entries.is_empty() {
+        true => vec![DirectoryContentPath::default()],
+        false => args.entries.iter().map(|entry| DirectoryContentPath::from(entry.as_str())).collect(),
+    };
+    let mut success = true;
+    let mut files = Vec::new();
+    for entry in entries {
+        match entry_files(&content, &entry) {
+            Some(entry_files) => files.extend(entry_files),
+            None => {
+                match output {
+                    Output::Text => println_error(&format!("{entry} doesn't exist in the archive")),
+                    Output::Json => Record::new("verify", &entry).failed("FileDoesNotExist", "Entry doesn't exist in the archive").emit(),
+                }
+                success = false;
+            }
+        }
+    }
+    let bar = progress_bar(output);
+    bar.set_length(files.len() as u64);
+    let mut outcomes = Vec::with_capacity(files.len());
+    for src in files {
+        let result = decrypt_entry(&encrypted_file, &src, io::sink(), &private_key, &verification);
+        if output == Output::Json {
+            Record::new("verify", &src).checked(&result).emit();
+        }
+        outcomes.push(Outcome { path: src, result });
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    if output == Output::Text {
+        print_summary(&outcomes);
+    }
+    Ok(exit_code(success && outcomes.iter().all(Outcome::is_success)))
+}
+
+/// Output is saved only if the digest is valid and the requested signature check passed, existing file is left untouched otherwise
+fn decrypt_file(encrypted_file: &EncryptedFile, src: &DirectoryContentPath, out: &Path, private_key: &PrivateKey, verification: &Verification, output: Output) -> bool {
+    let dst_path = match file_target(src, out) {
+        Ok(dst_path) => dst_path,
+        Err(err) => {
+            match output {
+                Output::Text => println_error(&format!("{src} hasn't been decrypted - {err}")),
+                Output::Json => Record::new("decrypt", src).failed(err.kind(), err).emit(),
+            }
+            return false;
+        }
+    };
+    let record = Record::new("decrypt", src).destination(dst_path.display());
+    let success = match verification {
+        Verification::None => {
+            let result = decrypt_atomically(&dst_path, |dst| encrypted_file.decrypt_file(src, dst, private_key), |digest| *digest);
+            let success = matches!(result, Ok(true));
+            match output {
+                Output::Text => decrypted_file_output(result),
+                Output::Json => record.decrypted(&result).emit(),
+            }
+            success
+        }
+        Verification::Verify(public_key) => {
+            let result = decrypt_atomically(&dst_path, |dst| encrypted_file.decrypt_file_and_verify(src, dst, private_key, public_key), |(digest, signature)| *digest && signature.is_ok());
+            let success = matches!(result, Ok((true, Ok(()))));
+            match output {
+                Output::Text => decrypted_file_and_verify_output(result),
+                Output::Json => record.decrypted_and_verified(&result).emit(),
+            }
+            success
+        }
+        Verification::FindSigner(signers_list) => {
+            let result = decrypt_atomically(&dst_path, |dst| encrypted_file.decrypt_file_and_find_signer(src, dst, private_key, signers_list), |(digest, signer)| *digest && signer.is_some());
+            let success = matches!(result, Ok((true, Some(_))));
+            match output {
+                Output::Text => decrypted_file_and_find_signer_output(result),
+                Output::Json => record.decrypted_and_found_signer(&result).emit(),
+            }
+            success
+        }
+    };
+    if output == Output::Text {
+        println!("{src} {}", saved_to(success, &dst_path));
+    }
+    success
+}
+
+fn decrypt_directory(encrypted_file: &EncryptedFile, src: DirectoryContentPath, out: &Path, private_key: &PrivateKey, verification: &Verification, output: Output) -> bool {
+    match encrypted_file.get_directory_content_soft().ok_or(EncryptedFileError::ContentIsUnknown.into()).and_then(|content| directory_targets(content, &src, out)) {
+        Ok(targets) => decrypt_targets(encrypted_file, targets, private_key, verification, output),
+        Err(err) => {
+            match output {
+                Output::Text => println_error(&format!("Nothing has been decrypted from {src} - {err}")),
+                Output::Json => Record::new("decrypt", &src).failed(err.kind(), err).emit(),
+            }
+            false
+        }
+    }
+}
+
+fn decrypt_targets(encrypted_file: &EncryptedFile, targets: Vec<(DirectoryContentPath, PathBuf)>, private_key: &PrivateKey, verification: &Verification, output: Output) -> bool {
+    let bar = progress_bar(output);
+    bar.set_length(targets.len() as u64);
+    let mut success = true;
+    for (src, dst) in targets {
+        success &= match verification {
+            Verification::None => {
+                let res = decrypt_atomically(&dst, |file| encrypted_file.decrypt_file(&src, file, private_key), |digest| *digest);
+                match (output, &res) {
+                    (Output::Json, res) => Record::new("decrypt", &src).destination(dst.display()).decrypted(res).emit(),
+                    (Output::Text, Ok(true)) => bar.suspend(|| println!("{} saved to destination {:?} - digest is correct", src, dst)),
+                    (Output::Text, Ok(false)) => bar.suspend(|| println_error(&format!("{} NOT saved to destination {:?} - digest is INCORRECT", src, dst))),
+                    (Output::Text, Err(err)) => bar.suspend(|| println_error(&format!("Couldn't save {} to {:?} - {}", src, dst, err))),
+                }
+                matches!(res, Ok(true))
+            }
+            Verification::Verify(public_key) => {
+                let res = decrypt_atomically(&dst, |file| encrypted_file.decrypt_file_and_verify(&src, file, private_key, public_key), |(digest, signature)| *digest && signature.is_ok());
+                match (output, &res) {
+                    (Output::Json, res) => Record::new("decrypt", &src).destination(dst.display()).decrypted_and_verified(res).emit(),
+                    (Output::Text, Ok((digest, signature))) => bar.suspend(|| println!("{} {} - digest is {}, signature is {}", src, saved_to(*digest && signature.is_ok(), &dst),
+                        match digest {
+                            true => green_font("CORRECT"),
+                            false => error_font("INCORRECT"),
+                        },
+                        match signature.is_ok() {
+                            true => green_font("VALID"),
+                            false => error_font("INVALID"),
+                        })),
+                    (Output::Text, Err(EncryptedFileError::FileIsNotSigned)) => bar.suspend(|| println_error(&format!("File {} is not signed", src))),
+                    (Output::Text, Err(err)) => bar.suspend(|| println_error(&format!("Couldn't save {} to {:?} - {}", src, dst, err))),
+                }
+                matches!(res, Ok((true, Ok(()))))
+            }
+            Verification::FindSigner(signers_list) => {
+                let res = decrypt_atomically(&dst, |file| encrypted_file.decrypt_file_and_find_signer(&src, file, private_key, signers_list), |(digest, signer)| *digest && signer.is_some());
+                match (output, &res) {
+                    (Output::Json, res) => Record::new("decrypt", &src).destination(dst.display()).decrypted_and_found_signer(res).emit(),
+                    (Output::Text, Ok((digest, signer))) => bar.suspend(|| println!("{} {} - digest is {}, signer: {}", src, saved_to(*digest && signer.is_some(), &dst),
+                        match digest {
+                            true => green_font("VALID"),
+                            false => error_font("INVALID"),
+                        }, signer.to_owned().unwrap_or("<UNKNOWN>".to_owned()))),
+                    (Output::Text, Err(EncryptedFileError::FileIsNotSigned)) => bar.suspend(|| println_error(&format!("File {} is not signed", src))),
+                    (Output::Text, Err(err)) => bar.suspend(|| println_error(&format!("Couldn't save {} to {:?} - {}", src, dst, err))),
+                }
+                matches!(res, Ok((true, Some(_))))
+            }
+        };
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    success
+}
+
+fn key_new(args: KeyNewArgs, output: Output) -> Result<ExitCode, Failure> {
+    check_output(&args.out, args.force)?;
+    let password = read_password(args.password_file.as_deref())?;
+    let key = PrivateKey::new(args.bits).map_err(|err| Failure::new(args.out.display(), "AsymetricKeyError", format!("Unexpected error - {err}")))?;
+    let metadata = KeyMetadata { label: args.label, owner: args.owner, expires: args.expires, comment: args.comment, ..KeyMetadata::new() };
+    if !save_private_to(&KeyFile::new(&key, metadata), &args.out, password.as_deref()) {
+        return Err(save_failure(&args.out));
+    }
+    let fingerprint = key.fingerprint();
+    report(output, Record::new("key new", args.out.display()).fingerprint(fingerprint.clone()), &format!("Key saved, fingerprint: {fingerprint}"));
+    Ok(ExitCode::SUCCESS)
+}
+
+fn key_derive(args: KeyDeriveArgs, output: Output) -> Result<ExitCode, Failure> {
+    check_output(&args.out, args.force)?;
+    let password = read_password(args.password_file.as_deref())?;
+    let out_password = read_password(args.out_password_file.as_deref())?;
+    // Every key derived from the same private key shares its fingerprint
+    let saved = match args.to {
+        DerivedKey::RsaPublic => match read_with_password_or(&args.from, password.as_deref(), |key: PrivateKey| key.get_rsa_public_key()) {
+            Some(key) => save_to(&key, &args.out, out_password.as_deref()).then(|| key.fingerprint()),
+            None => return Err(key_failure(&args.from)),
+        },
+        // Public key keeps metadata of the private key, RSA keys are saved without it
+        to => match read_key_with_password::<PrivateKey>(&args.from, password.as_deref()) {
+            Some((key, Some(metadata))) if matches!(to, DerivedKey::Public) => save_to(&KeyFile::new(&key.get_public_key(), metadata), &args.out, out_password.as_deref()).then(|| key.fingerprint()),
+            Some((key, None)) if matches!(to, DerivedKey::Public) => save_to(&key.get_public_key(), &args.out, out_password.as_deref()).then(|| key.fingerprint()),
+            Some((key, _)) => save_private_to(key.get_rsa_private_key(), &args.out, out_password.as_deref()).then(|| key.fingerprint()),
+            None => return Err(key_failure(&args.from)),
+        },
+    };
+    let fingerprint = saved.ok_or_else(|| save_failure(&args.out))?;
+    report(output, Record::new("key derive", args.out.display()).fingerprint(fingerprint.clone()), &format!("Key saved, fingerprint: {fingerprint}"));
+    Ok(ExitCode::SUCCESS)
+}
+
+#[inline]
+fn is_empty_dir(dir: &Path) -> bool {
+    std::fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+/// Empty directory is a list without signers, SignersList writes its manifest only after the first signer is added
+fn open_signers_list(dir: &Path) -> Result<SignersList, Failure> {
+    match is_empty_dir(dir) {
+        true => SignersList::new(dir),
+        false => SignersList::open(dir),
+    }.map_err(|err| Failure::new(dir.display(), "SignersListError", format!("Unexpected error while opening a signers list - {err}")))
+}
+
+fn signers(command: SignersCommand, output: Output) -> Result<ExitCode, Failure> {
+    match command {
+        SignersCommand::Init { dir } => {
+            if dir.exists() && !is_empty_dir(&dir) {
+                return Err(Failure::new(dir.display(), "FileAlreadyExists", format!("{} already exists and isn't empty", dir.display())));
+            }
+            if let Err(err) = std::fs::create_dir_all(&dir) {
+                return Err(Failure::new(dir.display(), "IOError", format!("Couldn't create a directory for signers list - {}", err)));
+            }
+            if let Err(err) = SignersList::new(&dir) {
+                return Err(Failure::new(dir.display(), "SignersListError", format!("Unexpected error while creating a signers list - {err}")));
+            }
+            report(output, Record::new("signers init", dir.display()), "Signers list created");
+        }
+        SignersCommand::Add { dir, name, key_file, password_file } => {
+            let password = read_password(password_file.as_deref())?;
+            let mut signers_list = open_signers_list(&dir)?;
+            let key = read_with_password_or(&key_file, password.as_deref(), |key: PublicKey| key.get_rsa_public_key().to_owned()).ok_or_else(|| key_failure(&key_file))?;
+            if let Err(err) = signers_list.add_signer(&name, &key) {
+                return Err(Failure::new(dir.display(), "SignersListError", format!("Unexpected error while adding signer to the list - {err}")));
+            }
+            let fingerprint = key.fingerprint();
+            let record = Record { signer: Some(name), ..Record::new("signers add", dir.display()).fingerprint(fingerprint.clone()) };
+            report(output, record, &format!("Signer successfully added, fingerprint: {fingerprint}"));
+        }
+        SignersCommand::Rm { dir, name } => {
+            let mut signers_list = open_signers_list(&dir)?;
+            if let Err(err) = signers_list.delete_signer(&name) {
+                return Err(Failure::new(dir.display(), "SignersListError", format!("Unhandled error while trying to delete sigener - {err}")));
+            }
+            let record = Record { signer: Some(name), ..Record::new("signers rm", dir.display()) };
+            report(output, record, "Signer has been deleted");
+        }
+        SignersCommand::Ls { dir } => {
+            let signers_list = open_signers_list(&dir)?;
+            let mut signers: Vec<(&str, String)> = signers_list.into_iter().map(|(name, key)| (name, key.fingerprint())).collect();
+            signers.sort_unstable();
+            for (name, fingerprint) in signers {
+                let text = format!("{name}  {fingerprint}");
+                report(output, Record { signer: Some(name.to_owned()), ..Record::new("signers ls", dir.display()).fingerprint(fingerprint) }, &text);
+            }
+        }
+        SignersCommand::Export { dir, name, out, force } => {
+            check_output(&out, force)?;
+            let signers_list = open_signers_list(&dir)?;
+            let key = signers_list.get_signers_key(&name).map_err(|err| Failure::new(dir.display(), "SignersListError", format!("Unhandled error while trying to possess signer's key - {err}")))?;
+            if !save_to(&key, &out, None) {
+                return Err(save_failure(&out));
+            }
+            let fingerprint = key.fingerprint();
+            let record = Record { signer: Some(name), ..Record::new("signers export", out.display()).fingerprint(fingerprint.clone()) };
+            report(output, record, &format!("Key saved, fingerprint: {fingerprint}"));
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -1,13 +1,21 @@
-use std::fs::File;
+use std::{collections::HashMap, fs::{File, create_dir_all}, io, path::{Path, PathBuf}};
 
-use dialoguer::{Select, FuzzySelect, MultiSelect};
+use dialoguer::{Confirm, Input, Select, FuzzySelect, MultiSelect};
 use indicatif::ProgressBar;
-use the_lock_lib::{EncryptedFile, directory_content::{DirectoryContent, DirectoryContentPath}, DecryptFileResult, DecryptFileAndVerifyResult, DecryptFileAndFindSignerResult, error::EncryptedFileError};
+use the_lock_lib::{EncryptedFile, directory_content::{DirectoryContent, DirectoryContentPath}, DecryptFileResult, DecryptFileAndVerifyResult, DecryptFileAndFindSignerResult, asymetric_key::PublicKey, error::{EncryptedFileError, EncryptedFileResult}, rsa::RsaPrivateKey};
 
-use crate::utils::{open_file, get_path, get_public_key, get_private_rsa_key, create_encrypted_file, open_encrypted_file, check_path, get_private_key, create_file_with_default, get_public_rsa_key, open_signer_list, list_content, create_file, get_zip_file_options, println_error, green_font, error_font};
+use crate::archive_browser::{browse, BrowserAction};
+use crate::archive_edit::{Conflict, Merged, rewrite, reopen, check_move, move_entry, merge, Rekey, rekey, add_file, stored_digests, differs_from_digest, content_changed};
+use crate::archive_info::ArchiveInfo;
+use crate::error::{OrCancel, check_interrupt};
+use crate::extraction::{Verification, Outcome, TargetError, entry_files, entry_targets, directory_targets, decrypt_atomically, decrypt_entry, is_verified, saved_to, print_summary};
+use crate::path_filter::{PathFilter, EntryGlobs, IGNORE_FILES};
+use crate::screen::{menu, pause};
+use crate::session::Session;
+use crate::utils::{open_file, get_path, create_encrypted_file, open_encrypted_file, check_path, create_file_with_default, get_public_rsa_key, get_public_key, create_file, get_zip_file_options, print_table, println_error, green_font, error_font};
 
 #[inline]
-fn get_encryption_mode() -> usize {
+fn get_encryption_mode() -> Option<usize> {
     Select::new()
         .items(&[
             "Encrypt",
@@ -16,11 +24,11 @@ fn get_encryption_mode() -> usize {
         ])
         .default(0)
         .interact()
-        .expect("IO error")
+        .or_cancel()
 }
 
 #[inline]
-fn get_decryption_mode() -> usize {
+fn get_decryption_mode() -> Option<usize> {
     Select::new()
         .items(&[
             "Decrypt",
@@ -30,30 +38,32 @@ fn get_decryption_mode() -> usize {
         ])
         .default(0)
         .interact()
-        .expect("IO error")
+        .or_cancel()
 }
 
-pub fn handle_encrypted_file() {
+pub fn handle_encrypted_file(session: &Session) {
     let mut pos = 0;
     loop {
-        pos = Select::new()
-                .items(&[
-                    "Create new encrypted file",
-                    "Open encrypted file",
-                    "Exit",
-                ])
-                .default(pos)
-                .interact()
-                .expect("IO error");
+        pos = match menu(session, "Encrypted file", &[
+            "Create new encrypted file",
+            "Open encrypted file",
+            "Exit",
+        ], pos) {
+            Ok(pos) => pos,
+            Err(err) => {
+                println_error(&format!("Couldn't show menu - {err}"));
+                return;
+            }
+        };
         match pos {
-            0 => encrypted_file_interactions(match create_encrypted_file() {
-                Some(ef) => ef,
-                None => continue,
-            }),
-            1 => encrypted_file_interactions(match open_encrypted_file() {
-                Some(ef) => ef,
-                None => continue,
-            }),
+            0 => match create_encrypted_file() {
+                Some((path, ef)) => encrypted_file_interactions(session, &path, ef),
+                None => pause(),
+            },
+            1 => match open_encrypted_file() {
+                Some((path, ef)) => encrypted_file_interactions(session, &path, ef),
+                None => pause(),
+            },
             _ => return,
         }
     }
@@ -65,7 +75,7 @@ fn list_of_files_helper(content: &DirectoryContent, prefix: &str) -> Vec<String>
         ans.push(format!("{prefix}{}", name.as_str()));
     }
     for (name, directory) in  content.get_dir_iter() {
-        ans.append(&mut list_of_files_helper(directory, &format!("{prefix}/{name}")));
+        ans.append(&mut list_of_files_helper(directory, &format!("{prefix}{name}/")));
     }
     ans
 }
@@ -76,55 +86,873 @@ fn list_of_files(content: &DirectoryContent) -> Vec<String> {
 }
 
 #[inline]
-fn decrypted_file_output(result: DecryptFileResult) {
+pub fn decrypted_file_output(result: DecryptFileResult) {
     match result {
         Ok(true) => println!("File has been decrypted and it's digiest is valid"),
-        Ok(false) => println!("File has been decrypted but it's digest is invalid"),
+        Ok(false) => println_error("File has been decrypted but it's digest is invalid, it hasn't been saved"),
         Err(EncryptedFileError::AsymetricKeyError(err)) => println_error(&format!("Decryption error, probably key is invalid - {}", err)),
         Err(err) => println_error(&format!("Unhandled error while decrypting file - {err}")),
     }
 }
 
 #[inline]
-fn decrypted_file_and_find_signer_output(result: DecryptFileAndFindSignerResult) {
+pub fn decrypted_file_and_find_signer_output(result: DecryptFileAndFindSignerResult) {
     match result {
         Ok((true, Some(name))) => println!("File has been decrypted, it's digiest is valid, signer is: {name}"),
-        Ok((false, Some(name))) => println!("File has been decrypted, it's digiest is invalid, signer is: {name}"),
-        Ok((true, None)) => println!("File has been decrypted, it's digiest is valid, signer hasn't been found"),
-        Ok((false, None)) => println!("File has been decrypted, it's digiest is invalid, signer hasn't been found"),
+        Ok((false, Some(name))) => println_error(&format!("File has been decrypted, it's digiest is invalid, signer is: {name}, it hasn't been saved")),
+        Ok((true, None)) => println_error("File has been decrypted, it's digiest is valid, signer hasn't been found, it hasn't been saved"),
+        Ok((false, None)) => println_error("File has been decrypted, it's digiest is invalid, signer hasn't been found, it hasn't been saved"),
         Err(EncryptedFileError::AsymetricKeyError(err)) => println_error(&format!("Decryption error, probably key is invalid - {}", err)),
         Err(err) => println_error(&format!("Uhandled error while decrypting file - {err}")),
     }
 }
 
 #[inline]
-fn decrypted_file_and_verify_output(result: DecryptFileAndVerifyResult) {
+pub fn decrypted_file_and_verify_output(result: DecryptFileAndVerifyResult) {
     match result {
         Ok((true, Ok(()))) => println!("File has been decrypted, both digest and signature are valid"),
-        Ok((false, Ok(()))) => println!("File has been decrypted, signature is valid, but digest not"),
-        Ok((true, Err(err))) => println!("File has been decrypted, digest is valid, but signature not - {err}"),
-        Ok((false, Err(err))) => println!("File has been decrypted, digest nor signature are valid - {err}"),
+        Ok((false, Ok(()))) => println_error("File has been decrypted, signature is valid, but digest not, it hasn't been saved"),
+        Ok((true, Err(err))) => println_error(&format!("File has been decrypted, digest is valid, but signature not, it hasn't been saved - {err}")),
+        Ok((false, Err(err))) => println_error(&format!("File has been decrypted, digest nor signature are valid, it hasn't been saved - {err}")),
         Err(err) => println_error(&format!("Uhandled error while decrypting file - {err}")),
     }
 }
 
-fn encrypted_file_interactions(mut encrypted_file: EncryptedFile) {
-    let mut pos = 0;
+fn decrypt_file_interaction(session: &Session, encrypted_file: &EncryptedFile, src: DirectoryContentPath, mode: Option<usize>) {
+    let Some(name) = src.file_name() else {
+        println_error("Path of the file is empty");
+        return;
+    };
+    let private_key = match session.private_key() {
+        Some(key) => key,
+        None => return,
+    };
+    let mut dst = match create_file_with_default(name.to_owned()) {
+        Some(file) => file,
+        None => return,
+    };
+    let written = match mode.or_else(get_decryption_mode) {
+        Some(0) => {
+            let result = encrypted_file.decrypt_file(&src, dst.file(), &private_key);
+            let written = matches!(result, Ok(true));
+            decrypted_file_output(result);
+            written
+        }
+        Some(1) => {
+            let result = encrypted_file.decrypt_file_and_verify(&src, dst.file(), &private_key, &match get_public_rsa_key() {
+                Some(key) => key,
+                None => return,
+            });
+            let written = matches!(result, Ok((true, Ok(()))));
+            decrypted_file_and_verify_output(result);
+            written
+        }
+        Some(2) => {
+            let result = encrypted_file.decrypt_file_and_find_signer(&src, dst.file(), &private_key, &match session.signers_list() {
+                Some(sl) => sl,
+                None => return,
+            });
+            let written = matches!(result, Ok((true, Some(_))));
+            decrypted_file_and_find_signer_output(result);
+            written
+        }
+        _ => false,
+    };
+    match check_interrupt() {
+        Ok(()) if written => {
+            if let Err(err) = dst.persist() {
+                println_error(&format!("Couldn't save decrypted file - {err}"));
+            }
+        }
+        Ok(()) => (),
+        Err(err) => println_error(&format!("{err} - existing file has been left untouched")),
+    }
+}
+
+/// Lets directory decryption stop between files once Ctrl+C has been pressed
+fn not_interrupted(bar: &ProgressBar) -> bool {
+    match check_interrupt() {
+        Ok(()) => true,
+        Err(err) => {
+            bar.suspend(|| println_error(&format!("{err} - remaining files are skipped")));
+            false
+        }
+    }
+}
+
+fn decrypt_directory_interaction(session: &Session, encrypted_file: &mut EncryptedFile, src: DirectoryContentPath, mode: Option<usize>) {
+    let dst = match check_path("Output path") {
+        Some(path) => path,
+        None => return,
+    };
+    let private_key = match session.private_key() {
+        Some(mut key) => if let Err(err) = key.rsa_precomput() {
+            println_error(&format!("RSA precomputions failed - {}", err));
+            return;
+        }
+        else {
+            key
+        },
+        None => return,
+    };
+    let targets = match encrypted_file.get_directory_content().map_err(TargetError::from).and_then(|content| directory_targets(content, &src, &dst)) {
+        Ok(targets) => targets,
+        Err(err) => {
+            println_error(&format!("Nothing has been decrypted - {}", err));
+            return;
+        }
+    };
+    let encrypted_file = &*encrypted_file;
+    let bar = ProgressBar::new(targets.len() as u64);
+    let decrypt_unsigned = |src: &DirectoryContentPath, dst: &Path| {
+        bar.println(format!("File {} is not signed, decrypting it without verification", src));
+        let result = decrypt_atomically(dst, |file| encrypted_file.decrypt_file(src, file, &private_key), |digest| *digest);
+        bar.suspend(|| decrypted_file_output(result));
+    };
+    match mode.or_else(get_decryption_mode) {
+        Some(0) => {
+            for (src, dst) in targets.into_iter().take_while(|_| not_interrupted(&bar)) {
+                match decrypt_atomically(&dst, |file| encrypted_file.decrypt_file(&src, file, &private_key), |digest| *digest) {
+                    Ok(true) => bar.suspend(|| println!("{} saved to destination {:?} - digest is correct", src, dst)),
+                    Ok(false) => bar.suspend(|| println_error(&format!("{} NOT saved to destination {:?} - digest is INCORRECT", src, dst))),
+                    Err(err) => bar.suspend(|| println_error(&format!("Couldn't save {} to {:?} - {}", src, dst, err))),
+                }
+                bar.inc(1);
+            }
+        }
+        Some(1) => {
+            let public_key = match get_public_rsa_key() {
+                Some(key) => key,
+                None => return,
+            };
+            for (src, dst) in targets.into_iter().take_while(|_| not_interrupted(&bar)) {
+                match decrypt_atomically(&dst, |file| encrypted_file.decrypt_file_and_verify(&src, file, &private_key, &public_key), |(digest, signature)| *digest && signature.is_ok()) {
+                    Ok((digest, signature)) => bar.println(format!("{} {} - digest is {}, signature is {}", src, saved_to(digest && signature.is_ok(), &dst),
+                        match digest {
+                            true => green_font("CORRECT"),
+                            false => error_font("INCORRECT"),
+                        },
+                        match signature.is_ok() {
+                            true => green_font("VALID"),
+                            false => error_font("INVALID"),
+                        })),
+                    Err(EncryptedFileError::FileIsNotSigned) => decrypt_unsigned(&src, &dst),
+                    Err(err) => bar.println(format!("Couldn't save {} to {:?} - {}", src, dst, err)),
+                }
+                bar.inc(1);
+            }
+        }
+        Some(2) => {
+            let signers_list = match session.signers_list() {
+                Some(sl) => sl,
+                None => return,
+            };
+            for (src, dst) in targets.into_iter().take_while(|_| not_interrupted(&bar)) {
+                match decrypt_atomically(&dst, |file| encrypted_file.decrypt_file_and_find_signer(&src, file, &private_key, &signers_list), |(digest, signer)| *digest && signer.is_some()) {
+                    Ok((digest, signer)) => bar.println(format!("{} {} - digest is {}, signer: {}", src, saved_to(digest && signer.is_some(), &dst),
+                        match digest {
+                            true => green_font("VALID"),
+                            false => error_font("INVALID"),
+                        }, signer.unwrap_or("<UNKNOWN>".to_owned()))),
+                    Err(EncryptedFileError::FileIsNotSigned) => decrypt_unsigned(&src, &dst),
+                    Err(err) => bar.println(format!("Couldn't save {} to {:?} - {}", src, dst, err)),
+                }
+                bar.inc(1);
+            }
+        }
+        _ => return,
+    }
+    bar.finish_and_clear();
+    println!("Directory decrypted");
+}
+
+fn decrypt_entry_interaction(session: &Session, encrypted_file: &mut EncryptedFile, src: DirectoryContentPath, mode: Option<usize>) {
+    match encrypted_file.get_directory_content_soft().is_some_and(|content| content.get_dir(&src).is_some()) {
+        true => decrypt_directory_interaction(session, encrypted_file, src, mode),
+        false => decrypt_file_interaction(session, encrypted_file, src, mode),
+    }
+}
+
+fn get_verification(session: &Session) -> Option<Verification> {
+    match get_decryption_mode()? {
+        0 => Some(Verification::None),
+        1 => get_public_rsa_key().map(Verification::Verify),
+        2 => session.signers_list().map(Verification::FindSigner),
+        _ => None,
+    }
+}
+
+/// Decrypts `entries` keeping the archive's tree under the output directory, with a shared progress bar and verification mode
+fn extract_entries(session: &Session, encrypted_file: &EncryptedFile, entries: Vec<DirectoryContentPath>) {
+    let dst = match get_path("Output directory") {
+        Some(path) => Path::new(&path).to_path_buf(),
+        None => return,
+    };
+    if let Err(err) = create_dir_all(&dst) {
+        println_error(&format!("Couldn't create output directory - {err}"));
+        return;
+    }
+    let private_key = match session.private_key() {
+        Some(mut key) => if let Err(err) = key.rsa_precomput() {
+            println_error(&format!("RSA precomputions failed - {}", err));
+            return;
+        }
+        else {
+            key
+        },
+        None => return,
+    };
+    let verification = match get_verification(session) {
+        Some(verification) => verification,
+        None => return,
+    };
+    let targets = match entry_targets(entries, &dst) {
+        Ok(targets) => targets,
+        Err(err) => {
+            println_error(&format!("Nothing has been decrypted - {}", err));
+            return;
+        }
+    };
+    let bar = ProgressBar::new(targets.len() as u64);
+    let mut outcomes = Vec::with_capacity(targets.len());
+    for (src, dst) in targets.into_iter().take_while(|_| not_interrupted(&bar)) {
+        let result = decrypt_atomically(&dst, |file| decrypt_entry(encrypted_file, &src, file, &private_key, &verification), is_verified);
+        outcomes.push(Outcome { path: src, result });
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    print_summary(&outcomes);
+}
+
+fn extract_all_interaction(session: &Session, encrypted_file: &mut EncryptedFile) {
+    match encrypted_file.get_directory_content() {
+        Ok(content) => {
+            let entries = entry_files(content, &DirectoryContentPath::default()).unwrap_or_default();
+            extract_entries(session, encrypted_file, entries);
+        }
+        Err(err) => println_error(&format!("Couldn't retrive archive content - {}", err)),
+    }
+}
+
+/// Entries matching glob patterns, which can be previewed before they're used
+fn select_by_globs(encrypted_file: &mut EncryptedFile) -> Option<Vec<DirectoryContentPath>> {
+    let patterns = Input::<String>::new().with_prompt("Glob patterns, comma separated (e.g. **/*.env, configs/prod/*)").interact_text().or_cancel()?;
+    let globs = EntryGlobs::new(patterns.split(','));
+    if globs.is_empty() {
+        println_error("No patterns given");
+        return None;
+    }
+    let entries: Vec<_> = match encrypted_file.get_directory_content() {
+        Ok(content) => entry_files(content, &DirectoryContentPath::default()).unwrap_or_default().into_iter().filter(|entry| globs.matches(&entry.to_string())).collect(),
+        Err(err) => {
+            println_error(&format!("Couldn't retrive archive content - {}", err));
+            return None;
+        }
+    };
+    if entries.is_empty() {
+        println!("No entries match the patterns");
+        return None;
+    }
     loop {
-        pos = Select::new()
+        match Select::new()
+                .with_prompt(format!("{} entries match the patterns", entries.len()))
                 .items(&[
-                    "Add file",
-                    "Add directory",
-                    "Decrypt file",
-                    "Decrypt directory",
-                    "List Content",
-                    "Clone without",
-                    "Set zip file options",
+                    "Extract them",
+                    "Preview",
                     "Exit",
                 ])
-                .default(pos)
+                .default(0)
                 .interact()
-                .expect("IO error");
+                .or_cancel()? {
+            0 => return Some(entries),
+            1 => for entry in &entries {
+                println!("{entry}");
+            },
+            _ => return None,
+        }
+    }
+}
+
+fn extract_matching_interaction(session: &Session, encrypted_file: &mut EncryptedFile) {
+    let entries = match Select::new()
+            .with_prompt("Entries to extract")
+            .items(&[
+                "Matching glob patterns",
+                "Selected entries",
+                "Exit",
+            ])
+            .default(0)
+            .interact()
+            .or_cancel() {
+        Some(0) => select_by_globs(encrypted_file),
+        Some(1) => select_files(encrypted_file),
+        _ => return,
+    };
+    match entries {
+        Some(entries) if entries.is_empty() => println!("Nothing to extract"),
+        Some(entries) => extract_entries(session, encrypted_file, entries),
+        None => (),
+    }
+}
+
+/// Decrypts every file into a sink, so plaintext is never written anywhere
+fn verify_archive_interaction(session: &Session, encrypted_file: &mut EncryptedFile) {
+    let files = match encrypted_file.get_directory_content() {
+        Ok(content) => entry_files(content, &DirectoryContentPath::default()).unwrap_or_default(),
+        Err(err) => {
+            println_error(&format!("Couldn't retrive archive content - {}", err));
+            return;
+        }
+    };
+    let private_key = match session.private_key() {
+        Some(mut key) => if let Err(err) = key.rsa_precomput() {
+            println_error(&format!("RSA precomputions failed - {}", err));
+            return;
+        }
+        else {
+            key
+        },
+        None => return,
+    };
+    let verification = match get_verification(session) {
+        Some(verification) => verification,
+        None => return,
+    };
+    let encrypted_file = &*encrypted_file;
+    let bar = ProgressBar::new(files.len() as u64);
+    let mut outcomes = Vec::with_capacity(files.len());
+    for src in files.into_iter().take_while(|_| not_interrupted(&bar)) {
+        let result = decrypt_entry(encrypted_file, &src, io::sink(), &private_key, &verification);
+        outcomes.push(Outcome { path: src, result });
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    print_summary(&outcomes);
+}
+
+fn select_file(encrypted_file: &mut EncryptedFile, prompt: &str) -> Option<DirectoryContentPath> {
+    let content = list_of_files(match encrypted_file.get_directory_content() {
+        Ok(content) => content,
+        Err(err) => {
+            println_error(&format!("Couldn't read file content - {err}"));
+            return None;
+        }
+    });
+    if content.is_empty() {
+        println_error("Archive is empty");
+        return None;
+    }
+    let pos = FuzzySelect::new()
+        .with_prompt(prompt)
+        .items(&content)
+        .interact()
+        .or_cancel()?;
+    Some(DirectoryContentPath::from(content[pos].as_str()))
+}
+
+fn select_files(encrypted_file: &mut EncryptedFile) -> Option<Vec<DirectoryContentPath>> {
+    let files = list_of_files(match encrypted_file.get_directory_content() {
+        Ok(dc) => dc,
+        Err(err) => {
+            println_error(&format!("Unhandled error while getting file content - {err}"));
+            return None;
+        }
+    });
+    Some(MultiSelect::new()
+        .items(&files)
+        .interact()
+        .or_cancel()?
+        .into_iter()
+        .map(|pos| DirectoryContentPath::from(files[pos].as_str()))
+        .collect())
+}
+
+/// Saves the archive with `save` and reopens it
+/// `Some(None)` means that saving failed and the archive is untouched, `None` that it couldn't be reopened and mustn't be used anymore
+fn save_archive<R, F>(path: &Path, encrypted_file: &mut EncryptedFile, save: F) -> Option<Option<R>>
+    where F: FnOnce(&EncryptedFile) -> EncryptedFileResult<R> {
+    match save(encrypted_file) {
+        Ok(ans) => match reopen(path, encrypted_file) {
+            Ok(()) => Some(Some(ans)),
+            Err(err) => {
+                println_error(&format!("Archive has been saved but couldn't be reopened - {err}"));
+                None
+            }
+        },
+        Err(err) => {
+            println_error(&format!("Archive has been left untouched - {err}"));
+            Some(None)
+        }
+    }
+}
+
+/// Same as `save_archive` with `archive_edit::rewrite`
+#[inline]
+fn apply_rewrite<R, F>(path: &Path, encrypted_file: &mut EncryptedFile, to_delete: &Vec<DirectoryContentPath>, edit: F) -> Option<Option<R>>
+    where F: FnOnce(&mut EncryptedFile) -> EncryptedFileResult<R> {
+    save_archive(path, encrypted_file, |encrypted_file| rewrite(path, encrypted_file, to_delete, edit))
+}
+
+/// Returns `None` if the archive couldn't be reopened, `encrypted_file` mustn't be used anymore then
+fn remove_entries(path: &Path, encrypted_file: &mut EncryptedFile, files_to_delete: Vec<DirectoryContentPath>) -> Option<()> {
+    if files_to_delete.is_empty() {
+        println!("Nothing to remove");
+        return Some(());
+    }
+    if apply_rewrite(path, encrypted_file, &files_to_delete, |_| Ok(()))?.is_some() {
+        println!("{} entries have been removed", files_to_delete.len());
+    }
+    Some(())
+}
+
+/// Moves file or directory `from`, asking for it if it isn't given
+/// Returns `None` if the archive couldn't be reopened, `encrypted_file` mustn't be used anymore then
+fn move_entry_interaction(path: &Path, encrypted_file: &mut EncryptedFile, from: Option<DirectoryContentPath>) -> Option<()> {
+    let Some(from) = from.or_else(|| get_path("Entry to move (file or directory)").map(DirectoryContentPath::from)) else {
+        return Some(());
+    };
+    let Some(to) = get_path(&format!("New path of {from}")).map(DirectoryContentPath::from) else {
+        return Some(());
+    };
+    if let Err(err) = encrypted_file.get_directory_content().and_then(|content| check_move(content, &from, &to)) {
+        println_error(&format!("Couldn't move {from} to {to} - {err}"));
+        return Some(());
+    }
+    if save_archive(path, encrypted_file, |_| move_entry(path, &from, &to))?.is_some() {
+        println!("{from} has been moved to {to}");
+    }
+    Some(())
+}
+
+fn get_conflict_policy() -> Option<Conflict> {
+    match Select::new()
+            .with_prompt("When an entry already exists")
+            .items(&[
+                "Skip it",
+                "Overwrite it",
+                "Rename the merged one",
+                "Exit",
+            ])
+            .default(0)
+            .interact()
+            .or_cancel()? {
+        0 => Some(Conflict::Skip),
+        1 => Some(Conflict::Overwrite),
+        2 => Some(Conflict::Rename),
+        _ => None,
+    }
+}
+
+/// Copies all entries of another archive into this one
+/// Returns `None` if the archive couldn't be reopened, `encrypted_file` mustn't be used anymore then
+fn merge_interaction(path: &Path, encrypted_file: &mut EncryptedFile) -> Option<()> {
+    let Some((other_path, mut other)) = open_encrypted_file() else {
+        return Some(());
+    };
+    if other_path.canonicalize().ok() == path.canonicalize().ok() {
+        println_error("Archive can't be merged with itself");
+        return Some(());
+    }
+    let content = match encrypted_file.get_directory_content() {
+        Ok(content) => content.clone(),
+        Err(err) => {
+            println_error(&format!("Couldn't retrive archive content - {}", err));
+            return Some(());
+        }
+    };
+    let Some(conflict) = get_conflict_policy() else {
+        return Some(());
+    };
+    let Some(report) = save_archive(path, encrypted_file, |_| merge(path, &content, &mut other, &other_path, conflict))? else {
+        return Some(());
+    };
+    let rows: Vec<([String; 2], bool)> = report.iter().map(|(entry, merged)| ([entry.to_string(), match merged {
+        Merged::Added => "added".to_owned(),
+        Merged::Overwritten => "overwritten".to_owned(),
+        Merged::Renamed(to) => format!("added as {to}"),
+        Merged::Skipped => "skipped".to_owned(),
+    }], matches!(merged, Merged::Skipped))).collect();
+    print_table(["Entry", "Result"], &rows);
+    let skipped = report.iter().filter(|(_, merged)| matches!(merged, Merged::Skipped)).count();
+    println!("{} entries merged, {} skipped", report.len() - skipped, skipped);
+    Some(())
+}
+
+/// Rewraps entries for a new recipient into a new archive
+fn rekey_interaction(session: &Session, path: &Path, encrypted_file: &mut EncryptedFile) {
+    let entries = match Select::new()
+            .with_prompt("Entries to rekey")
+            .items(&[
+                "All entries",
+                "Selected entries",
+                "Exit",
+            ])
+            .default(0)
+            .interact()
+            .or_cancel() {
+        Some(0) => match encrypted_file.get_directory_content() {
+            Ok(content) => entry_files(content, &DirectoryContentPath::default()).unwrap_or_default(),
+            Err(err) => {
+                println_error(&format!("Couldn't retrive archive content - {}", err));
+                return;
+            }
+        },
+        Some(1) => match select_files(encrypted_file) {
+            Some(entries) => entries,
+            None => return,
+        },
+        _ => return,
+    };
+    if entries.is_empty() {
+        println!("Nothing to rekey");
+        return;
+    }
+    let private_key = match session.private_key() {
+        Some(mut key) => if let Err(err) = key.rsa_precomput() {
+            println_error(&format!("RSA precomputions failed - {}", err));
+            return;
+        }
+        else {
+            key
+        },
+        None => return,
+    };
+    println!("New recipient");
+    let public_key = match get_public_key() {
+        Some(key) => key,
+        None => return,
+    };
+    let content = encrypted_file.get_directory_content_soft();
+    let any_signed = entries.iter().any(|entry| content.and_then(|content| content.get_file(entry)).is_some_and(|file| file.is_signed()));
+    let signer = match any_signed {
+        true => match Confirm::new().with_prompt("Re-sign signed entries (otherwise original signatures are kept)").interact().or_cancel() {
+            Some(true) => match session.private_rsa_key() {
+                Some(mut key) => if let Err(err) = key.precompute() {
+                    println_error(&format!("RSA precomputions failed - {}", err));
+                    return;
+                }
+                else {
+                    Some(key)
+                },
+                None => return,
+            },
+            Some(false) => None,
+            None => return,
+        },
+        false => None,
+    };
+    println!("New archive");
+    let mut output = match create_file() {
+        Some(file) => file,
+        None => return,
+    };
+    if output.target().canonicalize().ok() == path.canonicalize().ok() {
+        println_error("New archive can't replace the rekeyed one");
+        return;
+    }
+    let bar = ProgressBar::new(entries.len() as u64);
+    let (mut rekeyed, mut failed) = (0, 0);
+    let keys = Rekey { private_key: &private_key, public_key: &public_key, signer: signer.as_ref() };
+    let result = rekey(path, encrypted_file, &entries, output.file(), &keys, |entry, result| {
+        match result {
+            Ok(()) => rekeyed += 1,
+            Err(err) => {
+                failed += 1;
+                bar.suspend(|| println_error(&format!("Couldn't rekey {entry} - {err}")));
+            }
+        }
+        bar.inc(1);
+        not_interrupted(&bar)
+    });
+    bar.finish_and_clear();
+    match result.and_then(|()| Ok(output.persist()?)) {
+        Ok(()) => println!("{rekeyed} entries rekeyed, {failed} failed"),
+        Err(err) => println_error(&format!("Couldn't save new archive - {err}")),
+    }
+}
+
+fn get_encryption_keys(session: &Session) -> Option<(PublicKey, Option<RsaPrivateKey>)> {
+    let public_key = session.public_key()?;
+    let private_key = match get_encryption_mode()? {
+        0 => None,
+        1 => {
+            let mut key = session.private_rsa_key()?;
+            if let Err(err) = key.precompute() {
+                println_error(&format!("RSA precomputions failed - {}", err));
+                return None;
+            }
+            Some(key)
+        }
+        _ => return None,
+    };
+    Some((public_key, private_key))
+}
+
+/// Replaces content of an existing entry, the rest of the archive stays as it was
+/// Returns `None` if the archive couldn't be reopened, `encrypted_file` mustn't be used anymore then
+fn replace_file(path: &Path, encrypted_file: &mut EncryptedFile, src: File, dst: &DirectoryContentPath, public_key: &PublicKey, private_key: Option<&RsaPrivateKey>) -> Option<()> {
+    if apply_rewrite(path, encrypted_file, &vec![dst.clone()], |edited| add_file(edited, src, dst, public_key, private_key))?.is_some() {
+        println!("Entry {dst} has been updated");
+    }
+    Some(())
+}
+
+/// Returns `None` if the archive couldn't be reopened, `encrypted_file` mustn't be used anymore then
+fn update_file_interaction(session: &Session, path: &Path, encrypted_file: &mut EncryptedFile) -> Option<()> {
+    let Some(dst) = select_file(encrypted_file, "Entry to update") else {
+        return Some(());
+    };
+    let Some(src) = open_file("Path to file with the new content") else {
+        return Some(());
+    };
+    let Some((public_key, private_key)) = get_encryption_keys(session) else {
+        return Some(());
+    };
+    replace_file(path, encrypted_file, src, &dst, &public_key, private_key.as_ref())
+}
+
+fn get_path_filter() -> Option<PathFilter> {
+    let include = Input::<String>::new().with_prompt("Include patterns, comma separated (empty includes everything)").allow_empty(true).interact_text().or_cancel()?;
+    let exclude = Input::<String>::new().with_prompt("Exclude patterns, comma separated (e.g. target/, *.tmp)").allow_empty(true).interact_text().or_cancel()?;
+    let ignore_files = Confirm::new().with_prompt(format!("Honor {} files", IGNORE_FILES.join(" and "))).default(true).interact().or_cancel()?;
+    Some(PathFilter::new(include.split(','), exclude.split(','), ignore_files))
+}
+
+/// Files of a source directory which pass filters, paired with their paths in the archive
+fn get_directory_files(prompt: &str) -> Option<Vec<(PathBuf, DirectoryContentPath)>> {
+    let src = check_path(prompt)?;
+    if !src.is_dir() {
+        println_error("It's not an directory");
+        return None;
+    }
+    let dst = DirectoryContentPath::from(get_path("Destination path")?);
+    match get_path_filter()?.files(&src, dst) {
+        Ok(files) => Some(files),
+        Err(err) => {
+            println_error(&format!("Couldn't read directory - {err}"));
+            None
+        }
+    }
+}
+
+fn add_directory_interaction(session: &Session, encrypted_file: &mut EncryptedFile) {
+    let Some(files) = get_directory_files("Path to directory which is suppoused to be encrypted") else {
+        return;
+    };
+    if files.is_empty() {
+        println!("No files match the filters");
+        return;
+    }
+    loop {
+        match Select::new()
+                .with_prompt(format!("{} files match the filters", files.len()))
+                .items(&[
+                    "Add them",
+                    "Preview (dry run)",
+                    "Exit",
+                ])
+                .default(0)
+                .interact()
+                .or_cancel() {
+            Some(0) => break,
+            Some(1) => for (src, dst) in &files {
+                println!("{} -> {}", src.display(), dst);
+            },
+            _ => return,
+        }
+    }
+    let Some((public_key, private_key)) = get_encryption_keys(session) else {
+        return;
+    };
+    let bar = ProgressBar::new(files.len() as u64);
+    let mut failed = 0;
+    for (src, dst) in files.iter().take_while(|_| not_interrupted(&bar)) {
+        match File::open(src).map_err(EncryptedFileError::from).and_then(|file| add_file(encrypted_file, file, dst, &public_key, private_key.as_ref())) {
+            Ok(()) => bar.println(format!("{:?} saved to dst {}", src, dst)),
+            Err(err) => {
+                failed += 1;
+                bar.suspend(|| println_error(&format!("Couldn't save {:?} to {} - {}", src, dst, err)));
+            }
+        }
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    match failed {
+        0 => println!("Directory encrypted"),
+        _ => println_error(&format!("{failed} of {} files couldn't be added", files.len())),
+    }
+}
+
+/// Files of a source directory which are new or differ from their archived versions
+struct DirectoryChanges {
+    files: Vec<(PathBuf, DirectoryContentPath)>,
+    replaced: Vec<DirectoryContentPath>,
+    unchanged: usize,
+}
+
+fn get_directory_changes(session: &Session, path: &Path, encrypted_file: &mut EncryptedFile) -> Option<DirectoryChanges> {
+    let sources = get_directory_files("Path to directory which is suppoused to be updated")?;
+    if let Err(err) = encrypted_file.get_directory_content() {
+        println_error(&format!("Couldn't retrive archive content - {}", err));
+        return None;
+    }
+    let by_content = match Select::new()
+            .with_prompt("Re-add files whose")
+            .items(&[
+                "Content differs from the stored digest",
+                "Content differs (archived files are decrypted)",
+                "Exit",
+            ])
+            .default(0)
+            .interact()
+            .or_cancel()? {
+        0 => false,
+        1 => true,
+        _ => return None,
+    };
+    let (digests, private_key) = match by_content {
+        false => match stored_digests(path) {
+            Ok(digests) => (digests, None),
+            Err(err) => {
+                println_error(&format!("Couldn't read archive - {err}"));
+                return None;
+            }
+        },
+        true => {
+            let mut key = session.private_key()?;
+            if let Err(err) = key.rsa_precomput() {
+                println_error(&format!("RSA precomputions failed - {}", err));
+                return None;
+            }
+            (HashMap::new(), Some(key))
+        }
+    };
+    let encrypted_file = &*encrypted_file;
+    let content = encrypted_file.get_directory_content_soft()?;
+    let mut changes = DirectoryChanges { files: Vec::new(), replaced: Vec::new(), unchanged: 0 };
+    let bar = ProgressBar::new(sources.len() as u64);
+    for (src, dst) in sources.into_iter().take_while(|_| not_interrupted(&bar)) {
+        bar.inc(1);
+        if content.get_file(&dst).is_none() {
+            changes.files.push((src, dst));
+            continue;
+        }
+        // Entries without a digest can't be compared, so they are re-added
+        let changed = match &private_key {
+            Some(key) => content_changed(encrypted_file, &src, &dst, key).unwrap_or(true),
+            None => digests.get(&dst.to_string()).is_none_or(|digest| differs_from_digest(&src, digest).unwrap_or(true)),
+        };
+        match changed {
+            true => {
+                changes.replaced.push(dst.clone());
+                changes.files.push((src, dst));
+            }
+            false => changes.unchanged += 1,
+        }
+    }
+    bar.finish_and_clear();
+    check_interrupt().or_cancel()?;
+    Some(changes)
+}
+
+/// Re-adds only new and changed files of a directory, all of them or none are saved
+/// Returns `None` if the archive couldn't be reopened, `encrypted_file` mustn't be used anymore then
+fn update_directory_interaction(session: &Session, path: &Path, encrypted_file: &mut EncryptedFile) -> Option<()> {
+    let Some(changes) = get_directory_changes(session, path, encrypted_file) else {
+        return Some(());
+    };
+    if changes.files.is_empty() {
+        println!("Everything is up to date ({} files unchanged)", changes.unchanged);
+        return Some(());
+    }
+    let Some((public_key, private_key)) = get_encryption_keys(session) else {
+        return Some(());
+    };
+    let bar = ProgressBar::new(changes.files.len() as u64);
+    let result = apply_rewrite(path, encrypted_file, &changes.replaced, |edited| {
+        for (src, dst) in &changes.files {
+            if check_interrupt().is_err() {
+                return Err(io::Error::from(io::ErrorKind::Interrupted).into());
+            }
+            if let Err(err) = File::open(src).map_err(EncryptedFileError::from).and_then(|file| add_file(edited, file, dst, &public_key, private_key.as_ref())) {
+                bar.suspend(|| println_error(&format!("Couldn't save {:?} to {} - {}", src, dst, err)));
+                return Err(err);
+            }
+            bar.println(format!("{:?} saved to dst {}", src, dst));
+            bar.inc(1);
+        }
+        Ok(())
+    });
+    bar.finish_and_clear();
+    if result?.is_some() {
+        println!("{} added, {} updated, {} unchanged", changes.files.len() - changes.replaced.len(), changes.replaced.len(), changes.unchanged);
+    }
+    Some(())
+}
+
+fn clone_without(encrypted_file: &EncryptedFile, files_to_delete: Vec<DirectoryContentPath>) {
+    let mut output_file = match create_file() {
+        Some(file) => file,
+        None => return,
+    };
+    match encrypted_file.delete_path(output_file.file(), &files_to_delete) {
+        Ok(()) => match output_file.persist() {
+            Ok(()) => println!("File has been copied with indicated files omited"),
+            Err(err) => println_error(&format!("Couldn't save copied file - {err}")),
+        },
+        Err(err) => println_error(&format!("Unhandled error while copying files - {err}")),
+    }
+}
+
+fn archive_info_interaction(path: &Path, encrypted_file: &mut EncryptedFile, zip_options: &str) {
+    let content = match encrypted_file.get_directory_content() {
+        Ok(content) => content,
+        Err(err) => {
+            println_error(&format!("Couldn't retrive archive content - {}", err));
+            return;
+        }
+    };
+    match ArchiveInfo::read(path, content) {
+        Ok(info) => info.print(zip_options),
+        Err(err) => println_error(&format!("Couldn't read archive - {err}")),
+    }
+}
+
+fn encrypted_file_interactions(session: &Session, path: &Path, mut encrypted_file: EncryptedFile) {
+    let mut zip_options = "Default".to_owned();
+    let mut pos = 0;
+    let mut returning = false;
+    loop {
+        if returning {
+            pause();
+        }
+        returning = true;
+        pos = match menu(session, "Encrypted file", &[
+            "Add file",
+            "Add directory",
+            "Update file",
+            "Update directory",
+            "Decrypt file",
+            "Decrypt directory",
+            "List Content",
+            "Remove entries",
+            "Move entry",
+            "Merge archive",
+            "Rekey",
+            "Clone without",
+            "Set zip file options",
+            "Extract everything",
+            "Extract matching entries",
+            "Verify archive",
+            "Archive info",
+            "Exit",
+        ], pos) {
+            Ok(pos) => pos,
+            Err(err) => {
+                println_error(&format!("Couldn't show menu - {err}"));
+                return;
+            }
+        };
         match pos {
             0 => {
                 println!("Add File");
@@ -132,271 +960,111 @@ fn encrypted_file_interactions(mut encrypted_file: EncryptedFile) {
                     Some(file) => file,
                     None => continue,
                 };
-                match src.metadata() {
-                    Ok(metadata) => encrypted_file.set_zip_file_options(encrypted_file.zip_file_options().large_file(metadata.len() >= 4*1024*1024*1024)),
-                    Err(err) => {
-                        println_error(&format!("Couldn't read file size - {err}"));
-                        continue;
-                    }
+                let dst_path = DirectoryContentPath::from(match get_path("Destination path") {
+                    Some(path) => path,
+                    None => continue,
+                });
+                let exists = encrypted_file.get_directory_content().is_ok_and(|content| content.get_file(&dst_path).is_some());
+                if exists && !Confirm::new().with_prompt(format!("Entry {dst_path} already exists. Replace it?")).interact().or_cancel().unwrap_or(false) {
+                    continue;
                 }
-                let dst_path = DirectoryContentPath::from(get_path("Destination path"));
-                let public_key = match get_public_key() {
-                    Some(key) => key,
+                let (public_key, private_key) = match get_encryption_keys(session) {
+                    Some(keys) => keys,
                     None => continue,
                 };
-                let result = match get_encryption_mode() {
-                    0 => encrypted_file.add_file(src, &dst_path, &public_key),
-                    1 => {
-                        encrypted_file.add_file_and_sign(src, &dst_path, &public_key, &match get_private_rsa_key() {
-                            Some(key) => key,
-                            None => continue,
-                        })
+                match exists {
+                    true => if replace_file(path, &mut encrypted_file, src, &dst_path, &public_key, private_key.as_ref()).is_none() {
+                        pause();
+                        return;
+                    },
+                    false => match add_file(&mut encrypted_file, src, &dst_path, &public_key, private_key.as_ref()) {
+                        Ok(()) => println!("File successfully added"),
+                        Err(err) => println_error(&format!("Error occured when trying to add a file - {err}")),
                     },
-                    _ => continue,
-                };
-                match result {
-                    Ok(()) => println!("File successfully added"),
-                    Err(err) => println_error(&format!("Error occured when trying to add a file - {err}")),
                 }
             }
             1 => {
                 println!("Add Directory");
-                    let src = match check_path("Path to directory which is suppoused to be encrypted") {
-                        Some(dir) => {
-                            if !dir.is_dir() {
-                                println_error(&format!("It's not an directory"));
-                                continue;
-                            }
-                            dir
-                        },
-                        None => continue,
-                    };
-                    let dst_path = DirectoryContentPath::from(get_path("Destination path"));
-                    let public_key = match get_public_key() {
-                        Some(key) => key,
-                        None => continue,
-                    };
-                    let bar = ProgressBar::new(0);
-                    let result =  match get_encryption_mode() {
-                        0 => encrypted_file.add_directory_callback(src, dst_path, &public_key, |len| bar.set_length(len as u64),
-                        |src, dst, res| {
-                            match res {
-                                Ok(()) => bar.println(format!("{:?} saved to dst {}", src, dst)),
-                                Err(err) => bar.println(format!("Couldn't save {:?} to {} - {}", src, dst, err)),
-                            }
-                            bar.inc(1);
-                        }, |success| match success {
-                            true => bar.finish_and_clear(),
-                            false => bar.finish_and_clear(),
-                        }),
-                        1 => encrypted_file.add_directory_and_sign_callback(src, dst_path, &public_key, &match get_private_rsa_key() {
-                            Some(mut key) => if let Err(err) = key.precompute() {
-                                println_error(&format!("RSA precomputions failed - {}", err));
-                                continue;
-                            }
-                            else {
-                                key
-                            },
-                            None => continue,
-                        }, |len| bar.set_length(len as u64), |src, dst, res| {
-                            match res {
-                                Ok(()) => bar.println(format!("{:?} saved to dst {}", src, dst)),
-                                Err(err) => bar.println(format!("Couldn't save {:?} to {} - {}", src, dst, err)),
-                            }
-                            bar.inc(1);
-                        }, |success| match success {
-                            true => bar.finish_and_clear(),
-                            false => bar.finish_and_clear(),
-                        }),
-                        _ => continue,
-                    };
-                    match result {
-                        Ok(_) => println!("Directory encrypted"),
-                        Err(err) => println_error(&format!("Error occured when trying to add a directory - {err}")),
-                    }
+                add_directory_interaction(session, &mut encrypted_file);
             }
-            2 => {
-                let src = DirectoryContentPath::from({
-                    let content = list_of_files(match encrypted_file.get_directory_content() {
-                        Ok(content) => content,
-                        Err(err) => {
-                            println_error(&format!("Couldn't read file content - {err}"));
-                            continue;
-                        }
-                    });
-                    content[FuzzySelect::new()
-                    .with_prompt("File to decrypt")
-                    .items(&content)
-                    .interact()
-                    .expect("IO error")].clone()
-                });
-                let private_key = match get_private_key() {
-                    Some(key) => key,
+            2 => if update_file_interaction(session, path, &mut encrypted_file).is_none() {
+                pause();
+                return;
+            },
+            3 => if update_directory_interaction(session, path, &mut encrypted_file).is_none() {
+                pause();
+                return;
+            },
+            4 => {
+                let src = match select_file(&mut encrypted_file, "File to decrypt") {
+                    Some(src) => src,
                     None => continue,
                 };
-                let dst = match create_file_with_default(src.file_name().expect("File has name?").to_owned()) { // TODO 
-                    Some(file) => file,
-                    None => continue,
-                };
-                match get_decryption_mode() {
-                    0 => decrypted_file_output(encrypted_file.decrypt_file(&src, dst, &private_key)),
-                    1 => decrypted_file_and_verify_output(encrypted_file.decrypt_file_and_verify(&src, dst, &private_key, &match get_public_rsa_key() {
-                            Some(key) => key,
-                            None => continue,
-                        })),
-                    2 => decrypted_file_and_find_signer_output(encrypted_file.decrypt_file_and_find_signer(&src, dst, &private_key, &match open_signer_list() {
-                            Some(sl) => sl,
-                            None => continue,
-                        })),
-                    _ => continue,
-                };
+                decrypt_file_interaction(session, &encrypted_file, src, None);
             }
-            3 => {
-                let src = DirectoryContentPath::from(get_path("Source path"));
-                let dst = match check_path("Output path") {
+            5 => {
+                let src = DirectoryContentPath::from(match get_path("Source path") {
                     Some(path) => path,
                     None => continue,
-                };
-                let private_key = match get_private_key() {
-                    Some(mut key) => if let Err(err) = key.rsa_precomput() {
-                        println_error(&format!("RSA precomputions failed - {}", err));
+                });
+                decrypt_directory_interaction(session, &mut encrypted_file, src, None);
+            }
+            6 => {
+                let action = match encrypted_file.get_directory_content() {
+                    Ok(dc) => browse(dc),
+                    Err(err) => {
+                        println_error(&format!("Unhandled error while getting file content - {err}"));
                         continue;
                     }
-                    else {
-                        key
-                    },
-                    None => continue,
                 };
-                if let Err(err) = encrypted_file.get_directory_content() {
-                    println_error(&format!("Couldn't retrive directory content - {}", err));
-                    continue;
+                match action {
+                    Ok(Some(BrowserAction::Decrypt(src))) => decrypt_entry_interaction(session, &mut encrypted_file, src, None),
+                    Ok(Some(BrowserAction::Verify(src))) => decrypt_entry_interaction(session, &mut encrypted_file, src, Some(1)),
+                    Ok(Some(BrowserAction::Remove(files_to_delete))) => if remove_entries(path, &mut encrypted_file, files_to_delete).is_none() {
+                        pause();
+                        return;
+                    },
+                    Ok(Some(BrowserAction::Move(from))) => if move_entry_interaction(path, &mut encrypted_file, Some(from)).is_none() {
+                        pause();
+                        return;
+                    },
+                    Ok(None) => (),
+                    Err(err) => println_error(&format!("Couldn't show archive content - {err}")),
                 }
-                let bar = ProgressBar::new(0);
-                match get_decryption_mode() {
-                    0 => {
-                        match encrypted_file.decrypt_directory_callback(src, dst, &private_key, |len| bar.set_length(len as u64),
-                        |src, dst, res| {
-                            bar.inc(1);
-                            match res {
-                                Ok(true) => bar.suspend(|| println!("{} saved to destination {:?} - digest is correct", src, dst)),
-                                Ok(false) => bar.suspend(|| println_error(&format!("{} saved to destination {:?} - digest is INCORRECT", src, dst))),
-                                Err(err) => bar.suspend(|| println_error(&format!("Couldn't save {} to {:?} - {}", src, dst, err))),
-                            }
-                        }, |_| bar.finish_and_clear()) {
-                            Ok(_) => println!("Directory decrypted"),
-                            Err(err) => println_error(&format!("Unhandled error while decrypting directory - {err}")),
-                        };
-                    }
-                    1 => {
-                        match encrypted_file.decrypt_directory_and_verify_callback(src, dst, &private_key, &match get_public_rsa_key() {
-                            Some(key) => key,
-                            None => continue,
-                        },
-                        |len| bar.set_length(len as u64),
-                        |src, dst, res| {
-                            bar.inc(1);
-                            match res {
-                                Ok((digest, signature)) => bar.println(format!("{} saved to dst {:?} - digest is {}, signature is {}", src, dst,
-                                    match digest {
-                                        true => green_font("CORRECT"),
-                                        false => error_font("INCORRECT"),
-                                    },
-                                    match signature.is_ok() {
-                                        true => green_font("VALID"),
-                                        false => error_font("INVALID"),
-                                    })),
-                                Err(EncryptedFileError::FileIsNotSigned) => {
-                                    bar.println(format!("File {} is not signed, decrypting it without verification", src));
-                                    let result = encrypted_file.decrypt_file(src, match File::create(dst) {
-                                        Ok(file) => file,
-                                        Err(err) => {
-                                            bar.suspend(|| println_error(&format!("Couldn't create file {} - {}", dst, err)));
-                                            return;
-                                        }
-                                    }, &private_key);
-                                    bar.suspend(|| decrypted_file_output(result));
-                                }
-                                Err(err) => bar.println(format!("Couldn't save {} to {:?} - {}", src, dst, err)),
-                            }
-                        },
-                        |_| bar.finish_and_clear()) {
-                            Ok(_) => println!("Directory decrypted"),
-                            Err(err) => println_error(&format!("Unhandled error while decrypting directory - {err}")),
-                        }
-                    }
-                    2 => {
-                        match encrypted_file.decrypt_directory_and_find_signer_callback(src, dst, &private_key, &match open_signer_list() {
-                            Some(sl) => sl,
-                            None => continue,
-                        },
-                        |len| bar.set_length(len as u64),
-                        |src, dst, res| {
-                            bar.inc(1);
-                            match res {
-                                Ok((digest, signer)) => bar.println(format!("{} saved to dst {:?} - digest is {}, signer: {}", src, dst, 
-                                    match digest {
-                                        true => green_font("VALID"),
-                                        false => error_font("INVALID"),
-                                    }, signer.to_owned().unwrap_or("<UNKNOWN>".to_owned()))),
-                                Err(EncryptedFileError::FileIsNotSigned) => {
-                                    bar.println(format!("File {} is not signed, decrypting it without verification", src));
-                                    let result = encrypted_file.decrypt_file(src, match File::create(dst) {
-                                        Ok(file) => file,
-                                        Err(err) => {
-                                            bar.println(format!("Couldn't create file {} - {}", dst, err));
-                                            return;
-                                        }
-                                    }, &private_key);
-                                    bar.suspend(|| decrypted_file_output(result));
-                                }
-                                Err(err) => bar.println(format!("Couldn't save {} to {:?} - {}", src, dst, err)),
-                            }
-                        },
-                        |_| bar.finish_and_clear()) {
-                            Ok(_) => println!("Directory decrypted"),
-                            Err(err) => println_error(&format!("Uhandled error while decrypting directory - {err}")),
-                        }
-                    }
-                    _ => continue,
-                };
             }
-            4 => list_content(match encrypted_file.get_directory_content() {
-                Ok(dc) => dc,
-                Err(err) => {
-                    println_error(&format!("Unhandled error while getting file content - {err}"));
-                    continue;
+            7 => {
+                if let Some(files_to_delete) = select_files(&mut encrypted_file) {
+                    if remove_entries(path, &mut encrypted_file, files_to_delete).is_none() {
+                        pause();
+                        return;
+                    }
                 }
-            }),
-            5 => {
-                let output_file = match create_file() {
-                    Some(file) => file,
-                    None => continue,
-                };
-                let files_to_delete: Vec<DirectoryContentPath> = {
-                    let files = list_of_files(match encrypted_file.get_directory_content() {
-                        Ok(dc) => dc,
-                        Err(err) => {
-                            println_error(&format!("Unhandled error while getting file content - {err}"));
-                            continue;
-                        }
-                    });
-                    MultiSelect::new()
-                        .items(&files)
-                        .interact()
-                        .expect("IO error")
-                        .into_iter()
-                        .map(|pos| DirectoryContentPath::from(files[pos].as_str()))
-                        .collect()
-                };
-                match encrypted_file.delete_path(output_file, &files_to_delete) {
-                    Ok(()) => println!("File has been copied with indicated files omited"),
-                    Err(err) => println_error(&format!("Unhandled error while copying files - {err}")),
+            }
+            8 => if move_entry_interaction(path, &mut encrypted_file, None).is_none() {
+                pause();
+                return;
+            },
+            9 => if merge_interaction(path, &mut encrypted_file).is_none() {
+                pause();
+                return;
+            },
+            10 => rekey_interaction(session, path, &mut encrypted_file),
+            11 => {
+                if let Some(files_to_delete) = select_files(&mut encrypted_file) {
+                    clone_without(&encrypted_file, files_to_delete);
                 }
             }
-            6 => {
+            12 => {
                 println!("!!! Zip file options lasts til you leave this menu !!!");
-                encrypted_file.set_zip_file_options(get_zip_file_options());
+                if let Some((options, description)) = get_zip_file_options() {
+                    encrypted_file.set_zip_file_options(options);
+                    zip_options = description;
+                }
             }
+            13 => extract_all_interaction(session, &mut encrypted_file),
+            14 => extract_matching_interaction(session, &mut encrypted_file),
+            15 => verify_archive_interaction(session, &mut encrypted_file),
+            16 => archive_info_interaction(path, &mut encrypted_file, &zip_options),
             _ => return,
         }
     }
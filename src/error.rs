@@ -0,0 +1,84 @@
+use std::{fmt::Display, io, sync::{atomic::{AtomicBool, Ordering}, Arc, LazyLock}};
+
+use console::Term;
+use signal_hook::{consts::SIGINT, flag};
+
+use crate::utils::println_error;
+
+/// Reason why an interaction has been abandoned
+#[derive(Debug)]
+pub enum Error {
+    /// User pressed Ctrl+C
+    Interrupted,
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        match value.kind() {
+            io::ErrorKind::Interrupted => Self::Interrupted,
+            _ => Self::Io(value),
+        }
+    }
+}
+
+impl From<dialoguer::Error> for Error {
+    fn from(value: dialoguer::Error) -> Self {
+        match value {
+            dialoguer::Error::IO(err) => err.into(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Interrupted => write!(f, "Interrupted"),
+            Self::Io(err) => write!(f, "IO error - {err}"),
+        }
+    }
+}
+
+static INTERRUPTED: LazyLock<Arc<AtomicBool>> = LazyLock::new(Arc::default);
+
+/// Makes Ctrl+C only raise a flag, so the current operation can finish and clean up
+/// Pressing it again before the flag is handled still terminates the process
+pub fn install_handler() -> io::Result<()> {
+    flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&INTERRUPTED))?;
+    flag::register(SIGINT, Arc::clone(&INTERRUPTED))?;
+    Ok(())
+}
+
+/// Fails if Ctrl+C has been pressed since the last check
+pub fn check_interrupt() -> Result<()> {
+    match INTERRUPTED.swap(false, Ordering::SeqCst) {
+        true => Err(Error::Interrupted),
+        false => Ok(()),
+    }
+}
+
+pub trait OrCancel<T> {
+    /// Reports the error and turns it into `None`, so caller can go back to the previous menu
+    fn or_cancel(self) -> Option<T>;
+}
+
+impl<T, E: Into<Error>> OrCancel<T> for std::result::Result<T, E> {
+    fn or_cancel(self) -> Option<T> {
+        match self.map_err(Into::into).and_then(|ans| check_interrupt().map(|()| ans)) {
+            Ok(ans) => Some(ans),
+            Err(Error::Interrupted) => {
+                // dialoguer doesn't restore the cursor when a prompt is interrupted
+                let _ = Term::stderr().show_cursor();
+                println!();
+                println!("Cancelled");
+                None
+            }
+            Err(err) => {
+                println_error(&err.to_string());
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::{fmt::Display, fs::{File, create_dir_all}, io::Write, path::{Component, Path, PathBuf}};
+
+use the_lock_lib::{EncryptedFile, asymetric_key::PrivateKey, directory_content::{DirectoryContent, DirectoryContentPath}, error::{EncryptedFileError, EncryptedFileResult}, rsa::RsaPublicKey, signers_list::SignersList};
+
+use crate::report::error_kind;
+use crate::utils::{PendingFile, print_table, error_font, green_font};
+
+/// How signatures of decrypted files are checked
+pub enum Verification {
+    None,
+    Verify(RsaPublicKey),
+    FindSigner(SignersList),
+}
+
+/// What has been found out about a signature of a single entry
+pub enum Signature {
+    NotChecked,
+    Unsigned,
+    Valid,
+    Invalid,
+    Signer(String),
+    UnknownSigner,
+}
+
+/// Result of decrypting a single entry
+pub struct Outcome {
+    pub path: DirectoryContentPath,
+    pub result: EncryptedFileResult<(bool, Signature)>,
+}
+
+impl Outcome {
+    #[inline]
+    pub fn is_success(&self) -> bool {
+        self.result.as_ref().is_ok_and(is_verified)
+    }
+}
+
+/// Digest is valid and so is the signature, if it has been checked
+#[inline]
+pub fn is_verified(result: &(bool, Signature)) -> bool {
+    matches!(result, (true, Signature::NotChecked | Signature::Valid | Signature::Signer(_)))
+}
+
+/// Why entries can't be paired with paths they're decrypted to
+#[derive(Debug)]
+pub enum TargetError {
+    Archive(EncryptedFileError),
+    /// Entry has an element like `..`, so it would be written outside of the output directory
+    Escapes(DirectoryContentPath),
+}
+
+impl From<EncryptedFileError> for TargetError {
+    fn from(value: EncryptedFileError) -> Self {
+        Self::Archive(value)
+    }
+}
+
+impl Display for TargetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Archive(err) => write!(f, "{err}"),
+            Self::Escapes(entry) => write!(f, "{entry} would be written outside of the output directory"),
+        }
+    }
+}
+
+impl TargetError {
+    /// Kind reported in machine readable output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Archive(err) => error_kind(err),
+            Self::Escapes(_) => "InvalidPath",
+        }
+    }
+}
+
+/// Whether `name` is a single normal path element, so joining it to a directory stays inside of that directory
+fn is_safe_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+}
+
+/// Entry without `.`, `..` or other special elements, it's safe to recreate it under any directory
+pub fn is_safe_entry(entry: &DirectoryContentPath) -> bool {
+    entry.iter().all(|name| is_safe_name(name))
+}
+
+/// Joins `names` to `dst`, refusing names which would leave it
+fn join_names<'a>(dst: &Path, names: impl IntoIterator<Item = &'a str>, entry: &DirectoryContentPath) -> Result<PathBuf, TargetError> {
+    names.into_iter().try_fold(dst.to_path_buf(), |target, name| match is_safe_name(name) {
+        true => Ok(target.join(name)),
+        false => Err(TargetError::Escapes(entry.clone())),
+    })
+}
+
+fn directory_files_helper(content: &DirectoryContent, src: &mut DirectoryContentPath, ans: &mut Vec<DirectoryContentPath>) {
+    for (name, _) in content.get_files_iter() {
+        src.push(name).expect("Names are not empty");
+        ans.push(src.clone());
+        src.pop();
+    }
+    for (name, dir) in content.get_dir_iter() {
+        src.push(name).expect("Names are not empty");
+        directory_files_helper(dir, src, ans);
+        src.pop();
+    }
+}
+
+/// Path the file `entry` is decrypted to, it's saved right in `dst` under its own name
+pub fn file_target(entry: &DirectoryContentPath, dst: &Path) -> Result<PathBuf, TargetError> {
+    join_names(dst, [entry.file_name().unwrap_or("content")], entry)
+}
+
+/// Entries paired with paths they're decrypted to, keeping the archive's tree under `dst`
+/// Fails if any of them would end up outside of `dst`, nothing should be decrypted then
+pub fn entry_targets(entries: Vec<DirectoryContentPath>, dst: &Path) -> Result<Vec<(DirectoryContentPath, PathBuf)>, TargetError> {
+    entries.into_iter().map(|entry| {
+        let target = join_names(dst, entry.iter().map(String::as_str), &entry)?;
+        Ok((entry, target))
+    }).collect()
+}
+
+/// Every file of the entry `src`, which is either a file or a directory
+pub fn entry_files(content: &DirectoryContent, src: &DirectoryContentPath) -> Option<Vec<DirectoryContentPath>> {
+    if content.get_file(src).is_some() {
+        return Some(vec![src.clone()]);
+    }
+    let mut ans = Vec::new();
+    directory_files_helper(content.get_dir(src)?, &mut src.clone(), &mut ans);
+    Some(ans)
+}
+
+/// Every file of directory `src` paired with the path it's decrypted to
+/// Layout is the same as `EncryptedFile::decrypt_directory` produces: `dst/<src name>/...`
+/// Fails if any of them would end up outside of `dst`, nothing should be decrypted then
+pub fn directory_targets(content: &DirectoryContent, src: &DirectoryContentPath, dst: &Path) -> Result<Vec<(DirectoryContentPath, PathBuf)>, TargetError> {
+    let dir = content.get_dir(src).ok_or(EncryptedFileError::DirectoryDoesNotExist)?;
+    if !dst.is_dir() {
+        return Err(EncryptedFileError::ThisIsNotADirectory.into());
+    }
+    let mut files = Vec::new();
+    directory_files_helper(dir, &mut src.clone(), &mut files);
+    files.into_iter().map(|entry| {
+        let relative = entry.iter().skip(src.len()).map(String::as_str);
+        let target = join_names(dst, [src.file_name().unwrap_or("content")].into_iter().chain(relative), &entry)?;
+        Ok((entry, target))
+    }).collect()
+}
+
+/// Runs `decrypt` on a temporary file which replaces `dst` only if decryption succeeded and `keep` accepts its result
+/// Otherwise the temporary file is removed and `dst` stays untouched, so files failing their checks never overwrite anything
+pub fn decrypt_atomically<R, F, K>(dst: &Path, decrypt: F, keep: K) -> EncryptedFileResult<R>
+    where F: FnOnce(&mut File) -> EncryptedFileResult<R>, K: FnOnce(&R) -> bool {
+    if let Some(parent) = dst.parent() {
+        create_dir_all(parent)?;
+    }
+    let mut file = PendingFile::create(Box::from(dst))?;
+    let ans = decrypt(file.file())?;
+    if keep(&ans) {
+        file.persist()?;
+    }
+    Ok(ans)
+}
+
+/// Describes where a decrypted file went, it's saved only if it passed its checks
+pub fn saved_to(saved: bool, dst: &Path) -> String {
+    match saved {
+        true => format!("saved to {:?}", dst),
+        false => format!("NOT saved to {:?}", dst),
+    }
+}
+
+/// Decrypts `src` checking it according to `verification`
+/// Unsigned files are decrypted anyway and reported as such
+pub fn decrypt_entry<O: Write>(encrypted_file: &EncryptedFile, src: &DirectoryContentPath, dst: O, private_key: &PrivateKey, verification: &Verification) -> EncryptedFileResult<(bool, Signature)> {
+    let signed = encrypted_file.get_directory_content_soft()
+        .and_then(|content| content.get_file(src))
+        .is_some_and(|file| file.is_signed());
+    match verification {
+        Verification::Verify(public_key) if signed => encrypted_file.decrypt_file_and_verify(src, dst, private_key, public_key)
+            .map(|(digest, signature)| (digest, match signature {
+                Ok(()) => Signature::Valid,
+                Err(_) => Signature::Invalid,
+            })),
+        Verification::FindSigner(signers_list) if signed => encrypted_file.decrypt_file_and_find_signer(src, dst, private_key, signers_list)
+            .map(|(digest, signer)| (digest, signer.map_or(Signature::UnknownSigner, Signature::Signer))),
+        Verification::None => encrypted_file.decrypt_file(src, dst, private_key).map(|digest| (digest, Signature::NotChecked)),
+        _ => encrypted_file.decrypt_file(src, dst, private_key).map(|digest| (digest, Signature::Unsigned)),
+    }
+}
+
+fn summary_row(outcome: &Outcome) -> [String; 4] {
+    let (digest, signature, result) = match &outcome.result {
+        Ok((digest, signature)) => (
+            match digest {
+                true => "valid",
+                false => "INVALID",
+            }.to_owned(),
+            match signature {
+                Signature::NotChecked => "-".to_owned(),
+                Signature::Unsigned => "NOT SIGNED".to_owned(),
+                Signature::Valid => "valid".to_owned(),
+                Signature::Invalid => "INVALID".to_owned(),
+                Signature::Signer(name) => format!("signed by {name}"),
+                Signature::UnknownSigner => "UNKNOWN SIGNER".to_owned(),
+            },
+            match outcome.is_success() {
+                true => "OK".to_owned(),
+                false => "FAILED".to_owned(),
+            },
+        ),
+        Err(err) => ("-".to_owned(), "-".to_owned(), format!("FAILED - {err}")),
+    };
+    [outcome.path.to_string(), digest, signature, result]
+}
+
+/// Prints a table with a row per entry followed by success and failure counts
+pub fn print_summary(outcomes: &[Outcome]) {
+    let rows: Vec<([String; 4], bool)> = outcomes.iter().map(|outcome| (summary_row(outcome), !outcome.is_success())).collect();
+    print_table(["Entry", "Digest", "Signature", "Result"], &rows);
+    let succeeded = outcomes.iter().filter(|outcome| outcome.is_success()).count();
+    println!("{} succeeded, {} failed", green_font(&succeeded.to_string()), error_font(&(outcomes.len() - succeeded).to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_targets_keep_tree_under_dst() {
+        let targets = entry_targets(vec![DirectoryContentPath::from("dir/file.txt")], Path::new("out")).unwrap();
+        assert_eq!(targets[0].1, Path::new("out").join("dir").join("file.txt"));
+    }
+
+    #[test]
+    fn entry_targets_refuse_parent_dir() {
+        let entries = vec![DirectoryContentPath::from("file.txt"), DirectoryContentPath::from("../evil.txt")];
+        assert!(matches!(entry_targets(entries, Path::new("out")), Err(TargetError::Escapes(_))));
+        assert!(entry_targets(vec![DirectoryContentPath::from("dir/../../evil.txt")], Path::new("out")).is_err());
+        assert!(entry_targets(vec![DirectoryContentPath::from("./file.txt")], Path::new("out")).is_err());
+    }
+
+    #[test]
+    fn file_target_refuses_parent_dir() {
+        assert_eq!(file_target(&DirectoryContentPath::from("dir/file.txt"), Path::new("out")).unwrap(), Path::new("out").join("file.txt"));
+        assert!(matches!(file_target(&DirectoryContentPath::from("dir/.."), Path::new("out")), Err(TargetError::Escapes(_))));
+        assert!(file_target(&DirectoryContentPath::from("."), Path::new("out")).is_err());
+    }
+}
@@ -1,48 +1,62 @@
 use dialoguer::Select;
 use the_lock_lib::{asymetric_key::{PrivateKey, PublicKey, MIN_RSA_KEY_SIZE}, rsa::{RsaPrivateKey, RsaPublicKey}};
 
-use crate::utils::{save, read, get_number_in_range, println_error};
+use crate::error::OrCancel;
+use crate::key_metadata::{self, KeyMetadata};
+use crate::keyring::read_private_rsa_key;
+use crate::openssh;
+use crate::pem;
+use crate::utils::{Fingerprint, save, save_key, save_private, save_private_key, read, read_key, check_path, change_password, get_private_key_file, get_number_in_range, println_error};
 
 pub fn handle_key() {
     let mut pos = 0;
     loop {
-        pos = Select::new()
+        pos = match Select::new()
         .items(&[
             "Create new private key",
             "Open existing private key",
             "Open existing public key",
             "Open existing private RSA key",
             "Open existing public RSA key",
+            "Change key file password",
             "Exit",
         ])
         .default(pos)
         .interact()
-        .expect("IO error");
+        .or_cancel() {
+            Some(pos) => pos,
+            None => return,
+        };
         match pos {
             0 => {
                 println!("Creating new private key");
                 private_key_interactions(match PrivateKey::new(
-                    get_number_in_range("Key size", MIN_RSA_KEY_SIZE..usize::MAX, MIN_RSA_KEY_SIZE)
-                    ) {
-                        Ok(ans) => ans,
+                    match get_number_in_range("Key size", MIN_RSA_KEY_SIZE..usize::MAX, MIN_RSA_KEY_SIZE) {
+                        Some(size) => size,
+                        None => continue,
+                    }) {
+                        Ok(ans) => {
+                            show_fingerprint(&ans);
+                            ans
+                        },
                         Err(err) => {
                             println_error(&format!("Unexpected error - {err}"));
                             continue;
                         }
-                    });
+                    }, Some(KeyMetadata::new()));
             },
             1 => {
-                if let Some(key) = read::<PrivateKey>("Private key path") {
-                    private_key_interactions(key);
+                if let Some((key, metadata)) = get_private_key_file() {
+                    private_key_interactions(key, metadata);
                 }
             },
             2 => {
-                if let Some(key) = read::<PublicKey>("Public key path") {
-                    public_key_interactions(key);
+                if let Some((key, metadata)) = read_key::<PublicKey>("Public key path") {
+                    public_key_interactions(key, metadata);
                 }
             },
             3 => {
-                if let Some(key) = read::<RsaPrivateKey>("Private RSA key path") {
+                if let Some(key) = check_path("Private RSA key path").and_then(|path| read_private_rsa_key(&path)) {
                     private_rsa_key_interactions(key);
                 }
             },
@@ -51,58 +65,79 @@ pub fn handle_key() {
                     public_rsa_key_interactions(key);
                 }
             },
+            5 => change_password(),
             _ => return,
         }
     }
 }
 
-fn private_key_interactions(key: PrivateKey) {
+#[inline]
+fn show_fingerprint<T: Fingerprint>(key: &T) {
+    println!("Fingerprint: {}", key.fingerprint());
+}
+
+fn private_key_interactions(key: PrivateKey, mut metadata: Option<KeyMetadata>) {
     let mut pos = 0;
     loop {
-        pos = Select::new()
+        pos = match Select::new()
         .items(&[
             "Save to",
             "Get public key",
             "Get private RSA key",
             "Get public RSA key",
+            "Show fingerprint",
+            "Edit metadata",
             "Exit",
         ])
         .default(pos)
         .interact()
-        .expect("IO error");
+        .or_cancel() {
+            Some(pos) => pos,
+            None => return,
+        };
         match pos {
             0 => {
                 println!("Saving Key");
-                match save(&key) {
-                    true => println!("Key saved"),
-                    false => println_error(&format!("Failed to save a key")),
+                if !save_private_key(&key, metadata.as_ref()) {
+                    println_error("Failed to save a key");
                 }
             },
-            1 => public_key_interactions(key.get_public_key()),
+            1 => public_key_interactions(key.get_public_key(), metadata.clone()),
             2 => private_rsa_key_interactions(key.get_rsa_private_key().to_owned()),
             3 => public_rsa_key_interactions(key.get_rsa_public_key()),
+            4 => show_fingerprint(&key),
+            5 => if let Some(edited) = key_metadata::edit(metadata.as_ref()) {
+                edited.print();
+                println!("Save the key to keep new metadata");
+                metadata = Some(edited);
+            },
             _ => return,
         }
     }
 }
 
-fn public_key_interactions(key: PublicKey) {
+fn public_key_interactions(key: PublicKey, metadata: Option<KeyMetadata>) {
     let mut pos = 0;
     loop {
-        pos = Select::new()
+        pos = match Select::new()
         .items(&[
             "Save to",
             "Get public RSA key",
+            "Show fingerprint",
             "Exit",
         ])
         .default(pos)
         .interact()
-        .expect("IO error");
+        .or_cancel() {
+            Some(pos) => pos,
+            None => return,
+        };
         match pos {
             0 => {
-                save(&key);
+                save_key(&key, metadata.as_ref());
             },
             1 => public_rsa_key_interactions(key.get_rsa_public_key().to_owned()),
+            2 => show_fingerprint(&key),
             _ => return,
         }
     }
@@ -111,20 +146,27 @@ fn public_key_interactions(key: PublicKey) {
 fn private_rsa_key_interactions(key: RsaPrivateKey) {
     let mut pos = 0;
     loop {
-        pos = Select::new()
+        pos = match Select::new()
         .items(&[
             "Save to",
+            "Export as PEM",
             "Get public RSA key",
+            "Show fingerprint",
             "Exit",
         ])
         .default(pos)
         .interact()
-        .expect("IO error");
+        .or_cancel() {
+            Some(pos) => pos,
+            None => return,
+        };
         match pos {
             0 => {
-                save(&key);
+                save_private(&key);
             },
-            1 => public_rsa_key_interactions(key.to_public_key()),
+            1 => pem::export_private_rsa_key(&key),
+            2 => public_rsa_key_interactions(key.to_public_key()),
+            3 => show_fingerprint(&key),
             _ => return,
         }
     }
@@ -133,18 +175,27 @@ fn private_rsa_key_interactions(key: RsaPrivateKey) {
 pub fn public_rsa_key_interactions(key: RsaPublicKey) {
     let mut pos = 0;
     loop {
-        pos = Select::new()
+        pos = match Select::new()
         .items(&[
             "Save to",
+            "Export as PEM",
+            "Export as OpenSSH (ssh-rsa)",
+            "Show fingerprint",
             "Exit",
         ])
         .default(pos)
         .interact()
-        .expect("IO error");
+        .or_cancel() {
+            Some(pos) => pos,
+            None => return,
+        };
         match pos {
             0 => {
                 save(&key);
             },
+            1 => pem::export_public_rsa_key(&key),
+            2 => openssh::export_public_rsa_key(&key),
+            3 => show_fingerprint(&key),
             _ => return,
         }
     }
@@ -0,0 +1,136 @@
+use dialoguer::Input;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use time::{Date, Month, OffsetDateTime};
+
+use crate::error::OrCancel;
+use crate::utils::{Fingerprint, error_font};
+
+/// Version of `KeyFile` layout written by this build
+const KEY_FILE_VERSION: u8 = 1;
+
+/// Human readable information about a key, timestamps are unix seconds
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KeyMetadata {
+    pub label: String,
+    pub owner: String,
+    pub created: i64,
+    pub expires: Option<i64>,
+    pub comment: String,
+}
+
+impl KeyMetadata {
+    /// Empty metadata created right now
+    pub fn new() -> Self {
+        Self { label: String::new(), owner: String::new(), created: OffsetDateTime::now_utc().unix_timestamp(), expires: None, comment: String::new() }
+    }
+
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= OffsetDateTime::now_utc().unix_timestamp())
+    }
+
+    pub fn print(&self) {
+        for (name, value) in [("Label", &self.label), ("Owner", &self.owner)] {
+            if !value.is_empty() {
+                println!("{name}: {value}");
+            }
+        }
+        println!("Created: {}", format_date(self.created));
+        match self.expires {
+            Some(expires) if self.is_expired() => println!("{}", error_font(&format!("Expired: {}", format_date(expires)))),
+            Some(expires) => println!("Expires: {}", format_date(expires)),
+            None => println!("Expires: never"),
+        }
+        if !self.comment.is_empty() {
+            println!("Comment: {}", self.comment);
+        }
+    }
+}
+
+/// `PrivateKey` or `PublicKey` saved together with its metadata
+/// Files written before it existed contain just the serialized key and are still read by `decode`
+#[derive(Serialize, Deserialize)]
+pub struct KeyFile<T> {
+    version: u8,
+    metadata: KeyMetadata,
+    key: T,
+}
+
+impl<T> KeyFile<T> {
+    #[inline]
+    pub fn new(key: T, metadata: KeyMetadata) -> Self {
+        Self { version: KEY_FILE_VERSION, metadata, key }
+    }
+}
+
+impl<T: Fingerprint> Fingerprint for KeyFile<&T> {
+    fn fingerprint(&self) -> String {
+        self.key.fingerprint()
+    }
+}
+
+/// Decodes a decrypted key file, metadata is `None` for files without `KeyFile` container
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<(T, Option<KeyMetadata>), rmp_serde::decode::Error> {
+    match rmp_serde::from_slice::<KeyFile<T>>(data) {
+        Ok(file) if file.version == KEY_FILE_VERSION => Ok((file.key, Some(file.metadata))),
+        Ok(file) => Err(rmp_serde::decode::Error::Syntax(format!("unsupported key file version {}", file.version))),
+        Err(_) => rmp_serde::from_slice(data).map(|key| (key, None)),
+    }
+}
+
+pub fn format_date(timestamp: i64) -> String {
+    match OffsetDateTime::from_unix_timestamp(timestamp) {
+        Ok(date) => format!("{}-{:02}-{:02} {:02}:{:02} UTC", date.year(), date.month() as u8, date.day(), date.hour(), date.minute()),
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+/// Parses `YYYY-MM-DD` into the timestamp of the start of that day (UTC)
+pub fn parse_date(text: &str) -> Option<i64> {
+    let mut parts = text.trim().splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = Month::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Date::from_calendar_date(year, month, day).ok().map(|date| date.midnight().assume_utc().unix_timestamp())
+}
+
+#[inline]
+fn input(prompt: &str, initial: &str) -> Option<String> {
+    Input::<String>::new().with_prompt(prompt).with_initial_text(initial).allow_empty(true).interact_text().or_cancel()
+}
+
+/// Asks for every field, current values can be edited in place
+/// Creation date is kept, keys without metadata get the current date
+pub fn edit(metadata: Option<&KeyMetadata>) -> Option<KeyMetadata> {
+    let mut ans = metadata.cloned().unwrap_or_else(KeyMetadata::new);
+    ans.label = input("Label", &ans.label)?;
+    ans.owner = Input::<String>::new()
+        .with_prompt("Owner email")
+        .with_initial_text(&ans.owner)
+        .allow_empty(true)
+        .validate_with(|owner: &String| match owner.is_empty() || owner.contains('@') {
+            true => Ok(()),
+            false => Err("It's not an email address"),
+        })
+        .interact_text()
+        .or_cancel()?;
+    let expires = ans.expires
+        .and_then(|expires| OffsetDateTime::from_unix_timestamp(expires).ok())
+        .map(|date| format!("{}-{:02}-{:02}", date.year(), date.month() as u8, date.day()))
+        .unwrap_or_default();
+    ans.expires = match Input::<String>::new()
+            .with_prompt("Expires (YYYY-MM-DD, empty for never)")
+            .with_initial_text(expires)
+            .allow_empty(true)
+            .validate_with(|date: &String| match date.trim().is_empty() || parse_date(date).is_some() {
+                true => Ok(()),
+                false => Err("It's not a valid date"),
+            })
+            .interact_text()
+            .or_cancel()? {
+        date if date.trim().is_empty() => None,
+        date => parse_date(&date),
+    };
+    ans.comment = input("Comment", &ans.comment)?;
+    Some(ans)
+}
@@ -0,0 +1,211 @@
+use std::{path::{Path, PathBuf}, sync::{Mutex, MutexGuard, PoisonError}, time::{Duration, Instant, SystemTime}};
+
+use dialoguer::Select;
+use the_lock_lib::{asymetric_key::PrivateKey, rsa::RsaPrivateKey};
+
+use crate::error::OrCancel;
+use crate::key_metadata::KeyMetadata;
+use crate::utils::{read_from, read_key_from, fingerprint};
+
+enum CachedKey {
+    Private(PrivateKey, Option<KeyMetadata>),
+    Rsa(RsaPrivateKey),
+}
+
+impl CachedKey {
+    #[inline]
+    fn rsa_private_key(&self) -> &RsaPrivateKey {
+        match self {
+            Self::Private(key, _) => key.get_rsa_private_key(),
+            Self::Rsa(key) => key,
+        }
+    }
+}
+
+/// Modification time and size of a key file, its cached key is dropped once they change
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = path.metadata().ok()?;
+        Some(Self { modified: metadata.modified().ok(), len: metadata.len() })
+    }
+}
+
+struct Entry {
+    path: PathBuf,
+    stamp: Option<FileStamp>,
+    fingerprint: String,
+    key: CachedKey,
+}
+
+impl Entry {
+    /// Whether the file still contains the cached key, e.g. it hasn't been saved over or had its password changed
+    #[inline]
+    fn is_current(&self) -> bool {
+        self.stamp.is_some() && FileStamp::of(&self.path) == self.stamp
+    }
+
+    #[inline]
+    fn label(&self) -> String {
+        format!("{} ({})", self.path.display(), self.fingerprint)
+    }
+}
+
+/// Unlocked private keys kept for the lifetime of the process
+struct Keyring {
+    entries: Vec<Entry>,
+    idle_timeout: Option<Duration>,
+    last_used: Option<Instant>,
+    /// Keys have been wiped because of inactivity and nothing has been cached since
+    expired: bool,
+}
+
+impl Keyring {
+    /// Wipes keys if the keyring has been idle for too long, returns whether it did
+    /// Nothing is printed, so it's safe to call while the menu is drawn
+    fn expire(&mut self) -> bool {
+        match (self.idle_timeout, self.last_used) {
+            (Some(timeout), Some(last_used)) if last_used.elapsed() >= timeout && !self.entries.is_empty() => {
+                self.entries.clear();
+                self.expired = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+static KEYRING: Mutex<Keyring> = Mutex::new(Keyring { entries: Vec::new(), idle_timeout: None, last_used: None, expired: false });
+
+/// Locks the keyring, wiping it first if it has been idle for too long
+fn lock() -> MutexGuard<'static, Keyring> {
+    let mut keyring = KEYRING.lock().unwrap_or_else(PoisonError::into_inner);
+    keyring.expire();
+    keyring
+}
+
+/// Same as `lock` but also counts as keyring activity, keys whose files have changed are dropped
+fn use_keyring() -> MutexGuard<'static, Keyring> {
+    let mut keyring = KEYRING.lock().unwrap_or_else(PoisonError::into_inner);
+    if keyring.expire() {
+        println!("Keyring has been wiped after {} minutes of inactivity", keyring.idle_timeout.unwrap_or_default().as_secs() / 60);
+    }
+    keyring.entries.retain(Entry::is_current);
+    keyring.last_used = Some(Instant::now());
+    keyring
+}
+
+#[inline]
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `stamp` has to be taken before the key is read, so a file changed meanwhile isn't cached as current
+fn insert(path: PathBuf, stamp: Option<FileStamp>, key: CachedKey) {
+    let fingerprint = fingerprint(&key.rsa_private_key().to_public_key());
+    let mut keyring = use_keyring();
+    keyring.entries.retain(|entry| entry.path != path);
+    keyring.entries.push(Entry { path, stamp, fingerprint, key });
+    keyring.expired = false;
+}
+
+/// Lets user pick one of cached keys
+/// `Some(None)` means a key should be loaded from a file and `None` that user cancelled
+fn select<T>(entries: Vec<(String, T)>) -> Option<Option<T>> {
+    if entries.is_empty() {
+        return Some(None);
+    }
+    let mut items: Vec<&str> = entries.iter().map(|(label, _)| label.as_str()).collect();
+    items.push("Load from file");
+    let pos = Select::new()
+        .with_prompt("Cached keys")
+        .items(&items)
+        .default(0)
+        .interact()
+        .or_cancel()?;
+    Some(entries.into_iter().nth(pos).map(|(_, key)| key))
+}
+
+/// Cached private key together with metadata of its file
+pub fn select_private_key_file() -> Option<Option<(PrivateKey, Option<KeyMetadata>)>> {
+    let entries = use_keyring().entries.iter().filter_map(|entry| match &entry.key {
+        CachedKey::Private(key, metadata) => Some((entry.label(), (key.clone(), metadata.clone()))),
+        CachedKey::Rsa(_) => None,
+    }).collect();
+    select(entries)
+}
+
+pub fn select_private_rsa_key() -> Option<Option<RsaPrivateKey>> {
+    let entries = use_keyring().entries.iter().map(|entry| (entry.label(), entry.key.rsa_private_key().clone())).collect();
+    select(entries)
+}
+
+/// Reads a private key, asking for password only if it isn't cached yet
+#[inline]
+pub fn read_private_key(path: &Path) -> Option<PrivateKey> {
+    read_private_key_file(path).map(|(key, _)| key)
+}
+
+/// Same as `read_private_key`, but keeps metadata of the key file
+pub fn read_private_key_file(path: &Path) -> Option<(PrivateKey, Option<KeyMetadata>)> {
+    let path = canonical(path);
+    let cached = use_keyring().entries.iter().find_map(|entry| match &entry.key {
+        CachedKey::Private(key, metadata) if entry.path == path => Some((key.clone(), metadata.clone())),
+        _ => None,
+    });
+    if cached.is_some() {
+        return cached;
+    }
+    let stamp = FileStamp::of(&path);
+    let (key, metadata) = read_key_from::<PrivateKey>(&path)?;
+    insert(path, stamp, CachedKey::Private(key.clone(), metadata.clone()));
+    Some((key, metadata))
+}
+
+/// Reads a private RSA key, asking for password only if it isn't cached yet
+pub fn read_private_rsa_key(path: &Path) -> Option<RsaPrivateKey> {
+    let path = canonical(path);
+    let cached = use_keyring().entries.iter().find_map(|entry| match &entry.key {
+        CachedKey::Rsa(key) if entry.path == path => Some(key.clone()),
+        _ => None,
+    });
+    if cached.is_some() {
+        return cached;
+    }
+    let stamp = FileStamp::of(&path);
+    let key = read_from::<RsaPrivateKey>(&path)?;
+    insert(path, stamp, CachedKey::Rsa(key.clone()));
+    Some(key)
+}
+
+pub fn set_idle_timeout(timeout: Option<Duration>) {
+    use_keyring().idle_timeout = timeout;
+}
+
+/// It's called while the menu is drawn, so it must not print anything
+pub fn description() -> Option<Vec<String>> {
+    let keyring = lock();
+    if keyring.entries.is_empty() && keyring.idle_timeout.is_none() {
+        return None;
+    }
+    let mut lines = vec![match keyring.expired {
+        true => "Keys wiped after inactivity".to_owned(),
+        false => format!("{} cached keys", keyring.entries.len()),
+    }];
+    if let Some(timeout) = keyring.idle_timeout {
+        lines.push(format!("Wiped after {} idle minutes", timeout.as_secs() / 60));
+    }
+    Some(lines)
+}
+
+/// Drops all cached keys
+pub fn wipe() {
+    let mut keyring = lock();
+    keyring.entries.clear();
+    keyring.expired = false;
+}
@@ -1,38 +1,71 @@
 extern crate dialoguer;
 extern crate the_lock_lib;
 extern crate serialize_with_password;
+extern crate clap;
 
+mod archive_browser;
+mod archive_edit;
+mod archive_info;
+mod cli;
+mod error;
+mod extraction;
+mod key_metadata;
+mod keyring;
+mod openssh;
+mod path_filter;
+mod pem;
+mod report;
+mod screen;
+mod session;
 mod utils;
 mod key_handler;
 mod signer_list_handler;
 mod encrypted_file_handler;
 
-use dialoguer::Select;
+use std::process::ExitCode;
+
+use clap::Parser;
+use cli::Cli;
 use encrypted_file_handler::handle_encrypted_file;
 use key_handler::handle_key;
+use session::{Session, handle_session};
 use signer_list_handler::handle_signers;
+use utils::println_error;
 
-/***
- * TODO catch some CTRL+C
- */
-
-fn main() {
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        return cli::run(command, cli.output);
+    }
+    if let Err(err) = error::install_handler() {
+        println_error(&format!("Couldn't install Ctrl+C handler - {err}"));
+    }
+    let mut session = Session::default();
     let mut pos = 0;
     loop {
-        pos = Select::new().items(&[
+        pos = match screen::menu(&session, "The Lock", &[
             "Encrypted File Manipulation",
             "Key Manipulation",
             "Signer List Manipulation",
+            "Session context",
             "Exit",
-        ])
-        .default(pos)
-        .interact()
-        .expect("IO error");
+        ], pos) {
+            Ok(pos) => pos,
+            Err(err) => {
+                println_error(&format!("Couldn't show menu - {err}"));
+                keyring::wipe();
+                return ExitCode::FAILURE;
+            }
+        };
         match pos {
-            0 => handle_encrypted_file(),
+            0 => handle_encrypted_file(&session),
             1 => handle_key(),
             2 => handle_signers(),
-            _ => return
+            3 => handle_session(&mut session),
+            _ => {
+                keyring::wipe();
+                return ExitCode::SUCCESS;
+            }
         }
     }
 }
@@ -0,0 +1,108 @@
+use std::path::Path;
+
+use dialoguer::{Confirm, Input, Password, Select};
+use ssh_key::{AuthorizedKeys, HashAlg, PrivateKey, PublicKey, private::RsaKeypair, public::KeyData};
+use the_lock_lib::rsa::{BigUint, RsaPrivateKey, RsaPublicKey};
+
+use crate::error::OrCancel;
+use crate::utils::{Fingerprint, read_text, save_text, decoded_key, println_error};
+
+/// OpenSSH shows its own fingerprint (`ssh-keygen -l`), which differs from ours
+fn print_openssh_fingerprint(key: &PublicKey) {
+    println!("OpenSSH fingerprint: {}", key.fingerprint(HashAlg::Sha256));
+}
+
+/// Reads an `ssh-rsa` key from a `.pub` or `authorized_keys` file, asking which one to use if there are more of them
+pub fn read_public_rsa_key(path: &Path) -> Option<RsaPublicKey> {
+    let text = read_text(path)?;
+    let mut keys = Vec::new();
+    for entry in AuthorizedKeys::new(&text) {
+        match entry {
+            Ok(entry) if entry.public_key().key_data().rsa().is_some() => keys.push(entry.public_key().clone()),
+            Ok(_) => (),
+            Err(err) => {
+                println_error(&format!("Couldn't decode {} - {err}", path.display()));
+                return None;
+            }
+        }
+    }
+    let key = match keys.len() {
+        0 => {
+            println_error(&format!("{} doesn't contain any ssh-rsa key", path.display()));
+            return None;
+        }
+        1 => keys.remove(0),
+        _ => {
+            let items: Vec<String> = keys.iter().map(|key| format!("{} ({})", key.comment(), key.fingerprint(HashAlg::Sha256))).collect();
+            let pos = Select::new().with_prompt("Key").items(&items).default(0).interact().or_cancel()?;
+            keys.swap_remove(pos)
+        }
+    };
+    print_openssh_fingerprint(&key);
+    decoded_key(path, RsaPublicKey::try_from(key.key_data().rsa().expect("Only RSA keys are kept")))
+}
+
+/// Reads an OpenSSH RSA private key, asking for the passphrase if it's protected
+pub fn read_private_rsa_key(path: &Path) -> Option<RsaPrivateKey> {
+    let text = read_text(path)?;
+    let mut key = match PrivateKey::from_openssh(&text) {
+        Ok(key) => key,
+        Err(err) => {
+            println_error(&format!("Couldn't decode {} - {err}", path.display()));
+            return None;
+        }
+    };
+    while key.is_encrypted() {
+        match key.decrypt(Password::new().with_prompt("Passphrase").interact().or_cancel()?) {
+            Ok(decrypted) => key = decrypted,
+            Err(ssh_key::Error::Crypto) => if !Confirm::new().with_prompt("Wrong passphrase. Try again?").default(true).interact().or_cancel()? {
+                return None;
+            },
+            Err(err) => {
+                println_error(&format!("Couldn't decrypt {} - {err}", path.display()));
+                return None;
+            }
+        }
+    }
+    let Some(keypair) = key.key_data().rsa() else {
+        println_error(&format!("{} is not an RSA key", path.display()));
+        return None;
+    };
+    print_openssh_fingerprint(key.public_key());
+    decoded_key(path, rsa_private_key(keypair))
+}
+
+/// `ssh-key` conversion passes `p` twice instead of `p` and `q`, so the key is built from its components here
+fn rsa_private_key(keypair: &RsaKeypair) -> Result<RsaPrivateKey, ssh_key::Error> {
+    let key = RsaPrivateKey::from_components(
+        BigUint::try_from(&keypair.public.n)?,
+        BigUint::try_from(&keypair.public.e)?,
+        BigUint::try_from(&keypair.private.d)?,
+        vec![BigUint::try_from(&keypair.private.p)?, BigUint::try_from(&keypair.private.q)?],
+    )?;
+    key.validate()?;
+    Ok(key)
+}
+
+/// Writes a single `ssh-rsa` line, which can be appended to `authorized_keys` or used as a `.pub` file
+pub fn export_public_rsa_key(key: &RsaPublicKey) {
+    let key_data = match ssh_key::public::RsaPublicKey::try_from(key) {
+        Ok(key_data) => KeyData::Rsa(key_data),
+        Err(err) => {
+            println_error(&format!("Couldn't encode a key - {err}"));
+            return;
+        }
+    };
+    let comment = match Input::<String>::new().with_prompt("Comment").allow_empty(true).interact_text().or_cancel() {
+        Some(comment) => comment,
+        None => return,
+    };
+    let ssh_key = PublicKey::new(key_data, comment);
+    match ssh_key.to_openssh() {
+        Ok(line) => if save_text(&format!("{line}\n")) {
+            println!("Key exported, fingerprint: {}", key.fingerprint());
+            print_openssh_fingerprint(&ssh_key);
+        },
+        Err(err) => println_error(&format!("Couldn't encode a key - {err}")),
+    }
+}
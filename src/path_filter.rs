@@ -0,0 +1,325 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use the_lock_lib::{directory_content::DirectoryContentPath, error::EncryptedFileResult};
+
+/// Files with gitignore-like rules honored in every directory when `PathFilter` uses ignore files
+pub const IGNORE_FILES: [&str; 2] = [".gitignore", ".lockignore"];
+
+/// Single gitignore-like pattern
+/// `*` and `?` don't match `/`, `**` matches any number of directories, `[a-z]` and `[!a]` match a single character
+/// Pattern without `/` matches names at any depth, trailing `/` matches only directories and leading `!` negates the rule
+struct Rule {
+    glob: Vec<char>,
+    anchored: bool,
+    dir_only: bool,
+    negated: bool,
+}
+
+impl Rule {
+    fn parse(pattern: &str) -> Option<Self> {
+        let pattern = pattern.trim();
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern.strip_prefix('\\').unwrap_or(pattern)),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        match pattern.is_empty() {
+            true => None,
+            false => Some(Self { glob: pattern.chars().collect(), anchored, dir_only, negated }),
+        }
+    }
+
+    /// `path` is relative to the directory the rule comes from
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let path = match self.anchored {
+            true => path,
+            false => path.rsplit('/').next().unwrap_or(path),
+        };
+        glob_match(&self.glob, &path.chars().collect::<Vec<_>>())
+    }
+
+    /// Whether the rule matches file `path` or any directory on the way to it
+    fn matches_file(&self, path: &str) -> bool {
+        self.matches(path, false) || path.match_indices('/').any(|(i, _)| self.matches(&path[..i], true))
+    }
+}
+
+fn glob_match(glob: &[char], path: &[char]) -> bool {
+    match glob {
+        [] => path.is_empty(),
+        ['*', '*', '/', rest @ ..] => glob_match(rest, path) || path.iter().enumerate().any(|(i, c)| *c == '/' && glob_match(rest, &path[i + 1..])),
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| glob_match(rest, &path[i..])),
+        ['*', rest @ ..] => (0..=path.len()).take_while(|&i| i == 0 || path[i - 1] != '/').any(|i| glob_match(rest, &path[i..])),
+        ['?', rest @ ..] => path.first().is_some_and(|c| *c != '/') && glob_match(rest, &path[1..]),
+        ['[', rest @ ..] => match path.first() {
+            Some(&c) if c != '/' => match class_match(rest, c) {
+                Some((len, matched)) => matched && glob_match(&rest[len..], &path[1..]),
+                None => c == '[' && glob_match(rest, &path[1..]),
+            },
+            _ => false,
+        },
+        ['\\', c, rest @ ..] | [c, rest @ ..] => path.first() == Some(c) && glob_match(rest, &path[1..]),
+    }
+}
+
+/// Matches `c` against a class which starts right after `[`
+/// Returns length of the class including `]` and whether `c` matches, `None` if the class isn't closed
+fn class_match(class: &[char], c: char) -> Option<(usize, bool)> {
+    let (negated, start) = match class.first() {
+        Some('!' | '^') => (true, 1),
+        _ => (false, 0),
+    };
+    let mut matched = false;
+    let mut i = start;
+    while i < class.len() {
+        if class[i] == ']' && i > start {
+            return Some((i + 1, matched != negated));
+        }
+        if i + 2 < class.len() && class[i + 1] == '-' && class[i + 2] != ']' {
+            matched |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        }
+        else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    None
+}
+
+fn parse_rules<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Vec<Rule> {
+    patterns.into_iter().filter_map(Rule::parse).collect()
+}
+
+/// Rules of ignore files found in a single directory
+struct IgnoreRules {
+    /// Directory relative to the source root, it's empty or ends with `/`
+    base: String,
+    rules: Vec<Rule>,
+}
+
+/// Returns number of ignore files read from `dir`
+fn read_ignore_files(dir: &Path, base: &str, ans: &mut Vec<IgnoreRules>) -> io::Result<usize> {
+    let mut count = 0;
+    for name in IGNORE_FILES {
+        let content = match fs::read_to_string(dir.join(name)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        let rules = parse_rules(content.lines().filter(|line| !line.starts_with('#')));
+        ans.push(IgnoreRules { base: base.to_owned(), rules });
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Appends name of `path` to `dst`, it fails for names which aren't UTF-8 or are made only of characters archive paths can't contain (e.g. non-ASCII)
+fn push_name(dst: &mut DirectoryContentPath, path: &Path) -> EncryptedFileResult<()> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("{} can't be added, {reason}", path.display())).into();
+    let name = path.file_name().ok_or_else(|| invalid("it has no name"))?;
+    let name = name.to_str().ok_or_else(|| invalid("its name isn't valid UTF-8"))?;
+    dst.push(name).map_err(|_| invalid("its name has no characters allowed in archive paths"))
+}
+
+/// Decides which files of a source directory are added to an archive
+/// Excluded directories aren't even walked, so nothing inside of them can be included again
+#[derive(Default)]
+pub struct PathFilter {
+    /// Files have to match at least one of them, every file does if there are none
+    include: Vec<Rule>,
+    /// Applied after ignore files, so they take precedence over them
+    exclude: Vec<Rule>,
+    ignore_files: bool,
+}
+
+impl PathFilter {
+    pub fn new<'a>(include: impl IntoIterator<Item = &'a str>, exclude: impl IntoIterator<Item = &'a str>, ignore_files: bool) -> Self {
+        Self { include: parse_rules(include), exclude: parse_rules(exclude), ignore_files }
+    }
+
+    /// `path` is relative to the source root, the last matching rule decides
+    fn excluded(&self, ignore: &[IgnoreRules], path: &str, is_dir: bool) -> bool {
+        let mut excluded = false;
+        let rules = ignore.iter()
+            .filter_map(|ignore| path.strip_prefix(&ignore.base).map(|path| (path, &ignore.rules)))
+            .chain([(path, &self.exclude)]);
+        for (path, rules) in rules {
+            for rule in rules.iter().filter(|rule| rule.matches(path, is_dir)) {
+                excluded = !rule.negated;
+            }
+        }
+        excluded
+    }
+
+    /// Pattern matching a directory includes everything inside of it
+    fn included(&self, path: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|rule| rule.matches_file(path))
+    }
+
+    fn files_helper(&self, src: &Path, base: &str, dst: &mut DirectoryContentPath, ignore: &mut Vec<IgnoreRules>, ans: &mut Vec<(PathBuf, DirectoryContentPath)>) -> EncryptedFileResult<()> {
+        let read = match self.ignore_files {
+            true => read_ignore_files(src, base, ignore)?,
+            false => 0,
+        };
+        let mut paths = src.read_dir()?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+        for path in paths {
+            // Excluded files are skipped even if their names can't be stored in the archive
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let relative = format!("{base}{name}");
+            let is_dir = path.is_dir();
+            if self.excluded(ignore, &relative, is_dir) {
+                continue;
+            }
+            push_name(dst, &path)?;
+            match is_dir {
+                true => self.files_helper(&path, &format!("{relative}/"), dst, ignore, ans)?,
+                false => if self.included(&relative) {
+                    ans.push((path.clone(), dst.clone()));
+                },
+            }
+            dst.pop();
+        }
+        ignore.truncate(ignore.len() - read);
+        Ok(())
+    }
+
+    /// Files of directory `src` which pass the filter paired with their paths in the archive, sorted by path
+    /// Layout is the same as `EncryptedFile::add_directory` produces: `dst/<src name>/...`
+    pub fn files(&self, src: &Path, mut dst: DirectoryContentPath) -> EncryptedFileResult<Vec<(PathBuf, DirectoryContentPath)>> {
+        push_name(&mut dst, src)?;
+        let mut ans = Vec::new();
+        self.files_helper(src, "", &mut dst, &mut Vec::new(), &mut ans)?;
+        Ok(ans)
+    }
+}
+
+/// Glob patterns selecting entries of an archive, they use the same syntax as `PathFilter` patterns
+/// Pattern matching a directory selects everything inside of it, the last matching pattern decides so `!` can deselect entries
+pub struct EntryGlobs(Vec<Rule>);
+
+impl EntryGlobs {
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Self {
+        Self(parse_rules(patterns))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn matches(&self, entry: &str) -> bool {
+        let mut matched = false;
+        for rule in &self.0 {
+            if rule.matches_file(entry) {
+                matched = !rule.negated;
+            }
+        }
+        matched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excluding(exclude: &[&str]) -> PathFilter {
+        PathFilter::new([], exclude.iter().copied(), false)
+    }
+
+    #[test]
+    fn double_star_matches_any_number_of_directories() {
+        let globs = EntryGlobs::new(["a/**/z.txt"]);
+        assert!(globs.matches("a/z.txt"));
+        assert!(globs.matches("a/b/c/z.txt"));
+        assert!(!globs.matches("b/a/z.txt"));
+        assert!(EntryGlobs::new(["**"]).matches("a/b/c"));
+        assert!(EntryGlobs::new(["**/*.rs"]).matches("src/bin/main.rs"));
+    }
+
+    #[test]
+    fn single_star_stays_in_one_directory() {
+        assert!(EntryGlobs::new(["src/*.rs"]).matches("src/main.rs"));
+        assert!(!EntryGlobs::new(["src/*.rs"]).matches("src/bin/main.rs"));
+    }
+
+    #[test]
+    fn last_matching_rule_decides_so_negation_reincludes() {
+        let globs = EntryGlobs::new(["*.log", "!keep.log"]);
+        assert!(globs.matches("logs/debug.log"));
+        assert!(!globs.matches("logs/keep.log"));
+        let filter = excluding(&["*.log", "!keep.log"]);
+        assert!(filter.excluded(&[], "debug.log", false));
+        assert!(!filter.excluded(&[], "keep.log", false));
+    }
+
+    #[test]
+    fn anchored_patterns_match_from_the_root() {
+        let filter = excluding(&["/build", "docs/*.tmp"]);
+        assert!(filter.excluded(&[], "build", true));
+        assert!(!filter.excluded(&[], "src/build", true));
+        assert!(filter.excluded(&[], "docs/a.tmp", false));
+        assert!(!filter.excluded(&[], "src/docs/a.tmp", false));
+        assert!(excluding(&["build"]).excluded(&[], "src/build", true));
+    }
+
+    #[test]
+    fn directory_only_patterns_skip_files() {
+        let filter = excluding(&["target/"]);
+        assert!(filter.excluded(&[], "target", true));
+        assert!(filter.excluded(&[], "sub/target", true));
+        assert!(!filter.excluded(&[], "target", false));
+    }
+
+    #[test]
+    fn directory_include_includes_its_files() {
+        let filter = PathFilter::new(["configs/", "src"], [], false);
+        assert!(filter.included("configs/app.toml"));
+        assert!(filter.included("sub/configs/deep/app.toml"));
+        assert!(filter.included("src/main.rs"));
+        assert!(!filter.included("configs"));
+        assert!(!filter.included("docs/readme.md"));
+        assert!(PathFilter::new(["/configs/"], [], false).included("configs/app.toml"));
+        assert!(!PathFilter::new(["/configs/"], [], false).included("sub/configs/app.toml"));
+    }
+
+    #[test]
+    fn ignore_file_rules_are_relative_to_their_directory() {
+        let ignore = [IgnoreRules { base: "sub/".to_owned(), rules: parse_rules(["/out"]) }];
+        assert!(excluding(&[]).excluded(&ignore, "sub/out", true));
+        assert!(!excluding(&[]).excluded(&ignore, "out", true));
+    }
+
+    #[test]
+    fn names_without_allowed_characters_are_an_error() {
+        let src = std::env::temp_dir().join(format!("path-filter-test-{}", std::process::id()));
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("日本"), "").unwrap();
+        let result = excluding(&[]).files(&src, DirectoryContentPath::default());
+        fs::remove_dir_all(&src).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_which_are_not_utf8_are_an_error_unless_excluded() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let src = std::env::temp_dir().join(format!("path-filter-utf8-test-{}", std::process::id()));
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join(OsStr::from_bytes(b"bad\xff.bin")), "").unwrap();
+        fs::write(src.join("good.txt"), "").unwrap();
+        let excluded = excluding(&["*.bin"]).files(&src, DirectoryContentPath::default());
+        let included = excluding(&[]).files(&src, DirectoryContentPath::default());
+        fs::remove_dir_all(&src).unwrap();
+        assert_eq!(excluded.unwrap().len(), 1);
+        assert!(included.unwrap_err().to_string().contains("bad"));
+    }
+}
@@ -0,0 +1,177 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use the_lock_lib::{error::{EncryptedFileError, EncryptedFileResult}, DecryptFileResult, DecryptFileAndVerifyResult, DecryptFileAndFindSignerResult};
+
+use crate::extraction::Signature;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Output {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Single entry of the machine readable output, printed as one JSON line
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Record {
+    pub operation: &'static str,
+    pub path: String,
+    pub destination: Option<String>,
+    pub digest_valid: Option<bool>,
+    pub signature_valid: Option<bool>,
+    pub signer: Option<String>,
+    pub fingerprint: Option<String>,
+    pub error: Option<&'static str>,
+    pub message: Option<String>,
+}
+
+impl Record {
+    pub fn new(operation: &'static str, path: impl ToString) -> Self {
+        Self { operation, path: path.to_string(), ..Default::default() }
+    }
+
+    pub fn destination(mut self, destination: impl ToString) -> Self {
+        self.destination = Some(destination.to_string());
+        self
+    }
+
+    pub fn fingerprint(mut self, fingerprint: String) -> Self {
+        self.fingerprint = Some(fingerprint);
+        self
+    }
+
+    pub fn failed(mut self, error: &'static str, message: impl ToString) -> Self {
+        self.error = Some(error);
+        self.message = Some(message.to_string());
+        self
+    }
+
+    fn encrypted_file_error(self, err: &EncryptedFileError) -> Self {
+        self.failed(error_kind(err), err)
+    }
+
+    /// Sets error of a file which has been decrypted but failed its checks, invalid digest takes precedence over the signature
+    fn check_failed(mut self, digest: bool, signature_error: Option<(&'static str, String)>) -> Self {
+        self.digest_valid = Some(digest);
+        match (digest, signature_error) {
+            (false, _) => self.failed("InvalidDigest", "Digest is incorrect"),
+            (true, Some((kind, message))) => self.failed(kind, message),
+            (true, None) => self,
+        }
+    }
+
+    pub fn encrypted(self, result: &EncryptedFileResult<()>) -> Self {
+        match result {
+            Ok(()) => self,
+            Err(err) => self.encrypted_file_error(err),
+        }
+    }
+
+    pub fn decrypted(self, result: &DecryptFileResult) -> Self {
+        match result {
+            Ok(digest) => self.check_failed(*digest, None),
+            Err(err) => self.encrypted_file_error(err),
+        }
+    }
+
+    pub fn decrypted_and_verified(mut self, result: &DecryptFileAndVerifyResult) -> Self {
+        match result {
+            Ok((digest, signature)) => {
+                self.signature_valid = Some(signature.is_ok());
+                self.check_failed(*digest, signature.as_ref().err().map(|err| ("InvalidSignature", err.to_string())))
+            }
+            Err(err) => self.encrypted_file_error(err),
+        }
+    }
+
+    pub fn decrypted_and_found_signer(mut self, result: &DecryptFileAndFindSignerResult) -> Self {
+        match result {
+            Ok((digest, signer)) => {
+                self.signature_valid = Some(signer.is_some());
+                self.signer = signer.to_owned();
+                self.check_failed(*digest, signer.is_none().then(|| ("UnknownSigner", "Signature doesn't match any signer from the list".to_owned())))
+            }
+            Err(err) => self.encrypted_file_error(err),
+        }
+    }
+
+    pub fn checked(mut self, result: &EncryptedFileResult<(bool, Signature)>) -> Self {
+        match result {
+            Ok((digest, signature)) => {
+                let signature_error = match signature {
+                    Signature::NotChecked => None,
+                    Signature::Unsigned => {
+                        self.signature_valid = Some(false);
+                        Some(("FileIsNotSigned", EncryptedFileError::FileIsNotSigned.to_string()))
+                    }
+                    Signature::Valid => {
+                        self.signature_valid = Some(true);
+                        None
+                    }
+                    Signature::Invalid => {
+                        self.signature_valid = Some(false);
+                        Some(("InvalidSignature", "Signature is invalid".to_owned()))
+                    }
+                    Signature::UnknownSigner => {
+                        self.signature_valid = Some(false);
+                        Some(("UnknownSigner", "Signature doesn't match any signer from the list".to_owned()))
+                    }
+                    Signature::Signer(name) => {
+                        self.signature_valid = Some(true);
+                        self.signer = Some(name.to_owned());
+                        None
+                    }
+                };
+                self.check_failed(*digest, signature_error)
+            }
+            Err(err) => self.encrypted_file_error(err),
+        }
+    }
+
+    pub fn emit(&self) {
+        println!("{}", serde_json::to_string(self).expect("Record is always serializable"));
+    }
+}
+
+pub fn error_kind(err: &EncryptedFileError) -> &'static str {
+    match err {
+        EncryptedFileError::FileAlreadyExists => "FileAlreadyExists",
+        EncryptedFileError::FileDoesNotExist => "FileDoesNotExist",
+        EncryptedFileError::FileIsNotSigned => "FileIsNotSigned",
+        EncryptedFileError::FileKeyIsMissing => "FileKeyIsMissing",
+        EncryptedFileError::FileContentIsMissing => "FileContentIsMissing",
+        EncryptedFileError::InvalidPath => "InvalidPath",
+        EncryptedFileError::DirectoryDoesNotExist => "DirectoryDoesNotExist",
+        EncryptedFileError::ThisIsNotADirectory => "ThisIsNotADirectory",
+        EncryptedFileError::ContentIsUnknown => "ContentIsUnknown",
+        EncryptedFileError::ZipError(_) => "ZipError",
+        EncryptedFileError::DirectoryContentError(_) => "DirectoryContentError",
+        EncryptedFileError::RSAError(_) => "RSAError",
+        EncryptedFileError::IOError(_) => "IOError",
+        EncryptedFileError::SymmetricCipherError(_) => "SymmetricCipherError",
+        EncryptedFileError::AsymetricKeyError(_) => "AsymetricKeyError",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_checks_set_error() {
+        assert_eq!(Record::default().checked(&Ok((true, Signature::Invalid))).error, Some("InvalidSignature"));
+        assert_eq!(Record::default().checked(&Ok((true, Signature::UnknownSigner))).error, Some("UnknownSigner"));
+        assert_eq!(Record::default().checked(&Ok((true, Signature::Unsigned))).error, Some("FileIsNotSigned"));
+        assert_eq!(Record::default().checked(&Ok((false, Signature::Valid))).error, Some("InvalidDigest"));
+        assert_eq!(Record::default().decrypted(&Ok(false)).error, Some("InvalidDigest"));
+        assert_eq!(Record::default().decrypted_and_found_signer(&Ok((true, None))).error, Some("UnknownSigner"));
+    }
+
+    #[test]
+    fn passed_checks_have_no_error() {
+        assert_eq!(Record::default().checked(&Ok((true, Signature::Valid))).error, None);
+        assert_eq!(Record::default().checked(&Ok((true, Signature::NotChecked))).error, None);
+        assert_eq!(Record::default().decrypted(&Ok(true)).error, None);
+        assert_eq!(Record::default().decrypted_and_found_signer(&Ok((true, Some("alice".to_owned())))).error, None);
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::{stdin, stdout, Stdout};
+
+use crossterm::{event::{self, Event, KeyCode, KeyEventKind, KeyModifiers}, terminal::{enable_raw_mode, disable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}, ExecutableCommand};
+use ratatui::{Terminal, Frame, backend::CrosstermBackend, layout::{Layout, Constraint, Rect}, style::{Style, Color, Modifier}, text::Line, widgets::{Block, Borders, List, ListState, Paragraph}};
+
+use crate::error::check_interrupt;
+use crate::keyring;
+use crate::session::Session;
+
+pub struct TerminalGuard {
+    pub terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl TerminalGuard {
+    pub fn new() -> std::io::Result<Self> {
+        enable_raw_mode()?;
+        stdout().execute(EnterAlternateScreen)?;
+        Ok(Self { terminal: Terminal::new(CrosstermBackend::new(stdout()))? })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = stdout().execute(LeaveAlternateScreen);
+    }
+}
+
+fn draw_context(frame: &mut Frame, area: Rect, title: &str, lines: Option<Vec<String>>) {
+    let text: Vec<Line> = match lines {
+        Some(lines) => lines.into_iter().map(Line::from).collect(),
+        None => vec![Line::styled("<not set>", Style::new().add_modifier(Modifier::DIM))],
+    };
+    frame.render_widget(Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title)), area);
+}
+
+fn draw_sidebar(frame: &mut Frame, area: Rect, session: &Session) {
+    let [private_area, public_area, signers_area, keyring_area] = Layout::vertical([
+        Constraint::Length(5),
+        Constraint::Length(5),
+        Constraint::Length(4),
+        Constraint::Length(4),
+    ]).areas(area);
+    draw_context(frame, private_area, "Active private key", session.private_key_description());
+    draw_context(frame, public_area, "Active public key", session.public_key_description());
+    draw_context(frame, signers_area, "Active signers list", session.signers_list_description());
+    draw_context(frame, keyring_area, "Keyring", keyring::description());
+}
+
+fn draw(frame: &mut Frame, session: &Session, title: &str, items: &[&str], state: &mut ListState) {
+    let [sidebar, main, help] = {
+        let [content, help] = Layout::vertical([Constraint::Min(18), Constraint::Length(1)]).areas(frame.size());
+        let [sidebar, main] = Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(content);
+        [sidebar, main, help]
+    };
+    draw_sidebar(frame, sidebar, session);
+    frame.render_stateful_widget(
+        List::new(items.iter().copied())
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            .highlight_symbol("> "),
+        main,
+        state,
+    );
+    frame.render_widget(Paragraph::new("↑/↓ move  Enter select  Esc back").style(Style::new().add_modifier(Modifier::DIM)), help);
+}
+
+/// Full-screen menu with the session context in a sidebar
+/// Esc or q picks the last item, which is always the way back
+pub fn menu(session: &Session, title: &str, items: &[&str], default: usize) -> std::io::Result<usize> {
+    // Ctrl+C pressed during an operation which has already finished has nothing left to cancel
+    let _ = check_interrupt();
+    let mut guard = TerminalGuard::new()?;
+    let mut state = ListState::default().with_selected(Some(default.min(items.len() - 1)));
+    loop {
+        guard.terminal.draw(|frame| draw(frame, session, title, items, &mut state))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let pos = state.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => state.select(Some(pos.checked_sub(1).unwrap_or(items.len() - 1))),
+            KeyCode::Down | KeyCode::Char('j') => state.select(Some((pos + 1) % items.len())),
+            KeyCode::Enter => return Ok(pos),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(items.len() - 1),
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(items.len() - 1),
+            _ => (),
+        }
+    }
+}
+
+/// Keeps output of the last operation on screen until user presses Enter
+pub fn pause() {
+    println!("{}", console::style("Press Enter to return to the menu").dim());
+    let _ = stdin().read_line(&mut String::new());
+}
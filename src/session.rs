@@ -0,0 +1,176 @@
+use std::{path::Path, time::Duration};
+
+use dialoguer::Select;
+use the_lock_lib::{asymetric_key::{PrivateKey, PublicKey}, rsa::{RsaPrivateKey, RsaPublicKey, traits::PublicKeyParts}, signers_list::SignersList};
+
+use crate::error::OrCancel;
+use crate::keyring;
+use crate::screen::{menu, pause};
+use crate::utils::{check_path, read_from, fingerprint, open_signer_list_at, get_private_key, get_public_key, get_private_rsa_key, open_signer_list, get_number_in_range, println_error};
+
+/// Value loaded once and reused by following operations
+struct Active<T> {
+    source: String,
+    value: T,
+}
+
+impl<T> Active<T> {
+    #[inline]
+    fn new(path: &Path, value: T) -> Self {
+        Self { source: path.display().to_string(), value }
+    }
+}
+
+/// Private key itself stays in the keyring, so it's wiped together with other cached keys
+struct ActivePrivateKey {
+    path: Box<Path>,
+    description: Vec<String>,
+}
+
+#[inline]
+fn key_description(source: &str, key: &RsaPublicKey) -> Vec<String> {
+    vec![source.to_owned(), format!("RSA {} bits", key.size() * 8), fingerprint(key)]
+}
+
+/// Keys and signers list which are used by default instead of asking for them every time
+#[derive(Default)]
+pub struct Session {
+    private_key: Option<ActivePrivateKey>,
+    public_key: Option<Active<PublicKey>>,
+    signers_list: Option<Active<SignersList>>,
+}
+
+impl Session {
+    pub fn private_key_description(&self) -> Option<Vec<String>> {
+        self.private_key.as_ref().map(|active| active.description.clone())
+    }
+
+    pub fn public_key_description(&self) -> Option<Vec<String>> {
+        self.public_key.as_ref().map(|active| key_description(&active.source, active.value.get_rsa_public_key()))
+    }
+
+    pub fn signers_list_description(&self) -> Option<Vec<String>> {
+        self.signers_list.as_ref().map(|active| vec![active.source.clone(), format!("{} signers", active.value.len())])
+    }
+
+    pub fn private_key(&self) -> Option<PrivateKey> {
+        match &self.private_key {
+            Some(active) => {
+                println!("Using active private key {}", active.path.display());
+                keyring::read_private_key(&active.path)
+            }
+            None => get_private_key(),
+        }
+    }
+
+    pub fn public_key(&self) -> Option<PublicKey> {
+        match &self.public_key {
+            Some(active) => {
+                println!("Using active public key {}", active.source);
+                Some(active.value.clone())
+            }
+            None => get_public_key(),
+        }
+    }
+
+    pub fn private_rsa_key(&self) -> Option<RsaPrivateKey> {
+        match &self.private_key {
+            Some(active) => {
+                println!("Using RSA key of active private key {}", active.path.display());
+                keyring::read_private_key(&active.path).map(|key| key.get_rsa_private_key().to_owned())
+            }
+            None => get_private_rsa_key(),
+        }
+    }
+
+    pub fn signers_list(&self) -> Option<SignersList> {
+        match &self.signers_list {
+            Some(active) => {
+                println!("Using active signers list {}", active.source);
+                Some(active.value.clone())
+            }
+            None => open_signer_list(),
+        }
+    }
+}
+
+fn load_private_key() -> Option<ActivePrivateKey> {
+    let path = check_path("Private key path")?;
+    let key = keyring::read_private_key(&path)?;
+    let description = key_description(&path.display().to_string(), &key.get_rsa_public_key());
+    Some(ActivePrivateKey { path, description })
+}
+
+fn load_public_key() -> Option<Active<PublicKey>> {
+    let from_private = match Select::new()
+            .items(&[
+                "From public key",
+                "From private key",
+                "Exit",
+            ])
+            .default(0)
+            .with_prompt("Public key source")
+            .interact()
+            .or_cancel()? {
+        0 => false,
+        1 => true,
+        _ => return None,
+    };
+    let path = check_path(if from_private { "Private key path" } else { "Public key path" })?;
+    let key = match from_private {
+        true => read_from::<PrivateKey>(&path).map(|key| key.get_public_key()),
+        false => read_from::<PublicKey>(&path),
+    };
+    key.map(|key| Active::new(&path, key))
+}
+
+fn load_signers_list() -> Option<Active<SignersList>> {
+    let path = check_path("Signers list path")?;
+    open_signer_list_at(&path).map(|signers_list| Active::new(&path, signers_list))
+}
+
+/// Replaces `slot` with a freshly loaded value, keeping the old one if loading fails
+fn set_active<T>(slot: &mut Option<T>, load: fn() -> Option<T>) {
+    match load() {
+        Some(active) => *slot = Some(active),
+        None => pause(),
+    }
+}
+
+pub fn handle_session(session: &mut Session) {
+    let mut pos = 0;
+    loop {
+        pos = match menu(session, "Session context", &[
+            "Set active private key",
+            "Set active public key",
+            "Set active signers list",
+            "Clear active private key",
+            "Clear active public key",
+            "Clear active signers list",
+            "Set keyring idle timeout",
+            "Wipe keyring",
+            "Exit",
+        ], pos) {
+            Ok(pos) => pos,
+            Err(err) => {
+                println_error(&format!("Couldn't show menu - {err}"));
+                return;
+            }
+        };
+        match pos {
+            0 => set_active(&mut session.private_key, load_private_key),
+            1 => set_active(&mut session.public_key, load_public_key),
+            2 => set_active(&mut session.signers_list, load_signers_list),
+            3 => session.private_key = None,
+            4 => session.public_key = None,
+            5 => session.signers_list = None,
+            6 => match get_number_in_range("Idle minutes before cached keys are wiped (0 disables)", 0..=u64::MAX / 60, 0) {
+                Some(0) => keyring::set_idle_timeout(None),
+                Some(minutes) => keyring::set_idle_timeout(Some(Duration::from_secs(minutes * 60))),
+                None => pause(),
+            },
+            7 => keyring::wipe(),
+            _ => return,
+        }
+    }
+}
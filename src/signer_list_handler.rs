@@ -1,13 +1,14 @@
 use dialoguer::{Select, Input, FuzzySelect};
 use the_lock_lib::signers_list::SignersList;
+use crate::error::OrCancel;
 use crate::key_handler::public_rsa_key_interactions;
 
-use crate::utils::{create_signers_list, open_signer_list, get_public_rsa_key, println_error};
+use crate::utils::{Fingerprint, create_signers_list, open_signer_list, get_public_rsa_key, print_table, println_error};
 
 pub fn handle_signers() {
     let mut pos = 0;
     loop {
-        pos = Select::new()
+        pos = match Select::new()
         .items(&[
             "Create new",
             "Open",
@@ -15,7 +16,10 @@ pub fn handle_signers() {
         ])
         .default(pos)
         .interact()
-        .expect("IO error");
+        .or_cancel() {
+            Some(pos) => pos,
+            None => return,
+        };
         match pos {
             0 => {
                 if let Some(sl) = create_signers_list() {
@@ -32,25 +36,29 @@ pub fn handle_signers() {
     }
 }
 
-fn choose_signer(signers_list: &SignersList) -> String {
+fn choose_signer(signers_list: &SignersList) -> Option<String> {
     let mut signers = Vec::new();
     for (name, _) in signers_list.into_iter() {
         signers.push(name);
     }
-    signers[
+    if signers.is_empty() {
+        println_error("Signers list is empty");
+        return None;
+    }
+    Some(signers[
         FuzzySelect::new()
             .items(&signers)
             .with_prompt("Choose signers to delete")
             .interact()
-            .expect("IO error")
-    ].to_owned()
+            .or_cancel()?
+    ].to_owned())
 }
 
 fn signers_list_manipulation(mut signers_list: SignersList) {
     let mut pos = 0;
     println!("Signer's list contains {} signers", signers_list.len());
     loop {
-        pos = Select::new()
+        pos = match Select::new()
                 .items(&[
                     "Add signer",
                     "List signers",
@@ -60,48 +68,59 @@ fn signers_list_manipulation(mut signers_list: SignersList) {
                 ])
                 .default(pos)
                 .interact()
-                .expect("IO error");
+                .or_cancel() {
+            Some(pos) => pos,
+            None => return,
+        };
         match pos {
             0 => {
-                let name = &Input::<String>::new()
+                let name = &match Input::<String>::new()
                 .with_prompt("Signer name")
                 .validate_with(|v: &String| -> Result<(), &str> {
-                    match signers_list.contains(&v) {
+                    match signers_list.contains(v) {
                         true => Err("Signer with such name already exists"),
                         false => Ok(())
                     }
                 })
-                .interact()
-                .expect("IO error");
-                match signers_list.add_signer(
-                    &name,
-                    &match get_public_rsa_key() {
-                        Some(key) => key,
-                        None => continue,
-                    },
-                ) {
-                    Ok(()) => println!("Signer successfully added"),
+                .interact_text()
+                .or_cancel() {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let key = match get_public_rsa_key() {
+                    Some(key) => key,
+                    None => continue,
+                };
+                match signers_list.add_signer(name, &key) {
+                    Ok(()) => println!("Signer successfully added, fingerprint: {}", key.fingerprint()),
                     Err(err) => println_error(&format!("Unexpected error while adding signer to the list - {err}")),
                 }
             },
             1 => {
                 println!("List of signers:");
-                for (name, _) in signers_list.into_iter() {
-                    println!("{name}");
-                }
+                let rows: Vec<([String; 2], bool)> = signers_list.into_iter().map(|(name, key)| ([name.to_owned(), key.fingerprint()], false)).collect();
+                print_table(["Name", "Fingerprint"], &rows);
             },
             2 => {
                 println!("Delete signer");
-                match signers_list.delete_signer(&choose_signer(&signers_list)) {
+                let name = match choose_signer(&signers_list) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                match signers_list.delete_signer(&name) {
                     Ok(()) => println!("Signer has been deleted"),
                     Err(err) => println_error(&format!("Unhandled error while trying to delete sigener - {err}")),
                 }
             },
             3 => {
                 println!("Extract signer's RSA public key");
-                match signers_list.get_signers_key(&choose_signer(&signers_list)) {
+                let name = match choose_signer(&signers_list) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                match signers_list.get_signers_key(&name) {
                     Ok(key) => {
-                        println!("Got RSA public key");
+                        println!("Got RSA public key, fingerprint: {}", key.fingerprint());
                         public_rsa_key_interactions(key);
                     },
                     Err(err) => println_error(&format!("Unhandled error while trying to possess signer's key - {err}")),
@@ -1,76 +1,275 @@
-use std::{path::Path, fs::{File, create_dir}, io::{Read, Write}, ops::RangeBounds};
+use std::{path::{Path, PathBuf}, fs::{File, OpenOptions, create_dir, remove_file, rename}, io::{Read, Write}, ops::RangeBounds, sync::atomic::{AtomicUsize, Ordering}};
 
 use dialoguer::{Confirm, Password, Input, Select};
-use serialize_with_password::{Serialize, Deserialize, serialize_serde_no_pass, serialize_serde, is_encrypted, deserialize_serde, deserialize_serde_no_pass};
-use the_lock_lib::{signers_list::SignersList, rsa::{RsaPublicKey, RsaPrivateKey}, asymetric_key::{PrivateKey, PublicKey}, EncryptedFile, directory_content::DirectoryContent, FileOptions};
+use sha2::{Digest, Sha256};
+use serialize_with_password::{Serialize, Deserialize, serialize, serialize_no_pass, deserialize, deserialize_no_pass, serialize_serde_no_pass, serialize_serde, is_encrypted};
+use the_lock_lib::{signers_list::SignersList, rsa::{RsaPublicKey, RsaPrivateKey, traits::PublicKeyParts}, asymetric_key::{PrivateKey, PublicKey}, EncryptedFile, FileOptions};
 
-fn delete_path<P: AsRef<Path>>(path: P) {
+use crate::error::OrCancel;
+use crate::keyring;
+use crate::key_metadata::{self, KeyFile, KeyMetadata};
+use crate::openssh;
+use crate::pem;
+
+fn delete_path<P: AsRef<Path>>(path: P) -> Option<()> {
     match (path.as_ref().is_file(), path.as_ref().is_dir()) {
-        (true, _) => std::fs::remove_file(path).expect("Could't delete file"),
-        (_, true) => std::fs::remove_dir_all(path).expect("Could't delete directory"),
-        _ => (),
+        (true, _) => std::fs::remove_file(path).map_err(|err| println_error(&format!("Could't delete file - {err}"))).ok(),
+        (_, true) => std::fs::remove_dir_all(path).map_err(|err| println_error(&format!("Could't delete directory - {err}"))).ok(),
+        _ => Some(()),
     }
 }
 
+/// Hidden path next to `target`, so renaming it over `target` never crosses filesystems
+fn temp_path(target: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = target.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    target.with_file_name(format!(".{name}.{}.{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// Makes sure the rename itself survives a crash, not every platform can open a directory
 #[inline]
-pub fn get_number_in_range<T, R: RangeBounds<T>>(prompt: &str, range: R, default: T) -> T 
+fn sync_parent(path: &Path) {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        let _ = File::open(parent).and_then(|dir| dir.sync_all());
+    }
+}
+
+/// Output file which is written to a temporary file and replaces its target only after `persist`
+/// When dropped earlier the temporary file is removed and the target stays untouched
+pub struct PendingFile {
+    target: Box<Path>,
+    temp: PathBuf,
+    file: File,
+    persisted: bool,
+}
+
+impl PendingFile {
+    pub fn create(target: Box<Path>) -> std::io::Result<Self> {
+        Self::open(target, OpenOptions::new().read(true).write(true).create_new(true))
+    }
+
+    /// Only the owner can read the file, it's meant for secrets which aren't encrypted
+    pub fn create_private(target: Box<Path>) -> std::io::Result<Self> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        Self::open(target, &options)
+    }
+
+    fn open(target: Box<Path>, options: &OpenOptions) -> std::io::Result<Self> {
+        let temp = temp_path(&target);
+        let file = options.open(&temp)?;
+        Ok(Self { target, temp, file, persisted: false })
+    }
+
+    #[inline]
+    pub fn file(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Path of the temporary file
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Path the file is moved to by `persist`
+    #[inline]
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    pub fn persist(mut self) -> std::io::Result<()> {
+        self.file.sync_all()?;
+        rename(&self.temp, &self.target)?;
+        self.persisted = true;
+        sync_parent(&self.target);
+        Ok(())
+    }
+}
+
+impl Drop for PendingFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = remove_file(&self.temp);
+        }
+    }
+}
+
+#[inline]
+pub fn get_number_in_range<T, R: RangeBounds<T>>(prompt: &str, range: R, default: T) -> Option<T> 
     where T: std::str::FromStr + std::fmt::Display + PartialEq + PartialOrd, <T as std::str::FromStr>::Err: std::fmt::Debug {
-    Input::<String>::new().with_prompt(prompt).default(default.to_string()).validate_with(|v: &String| -> Result<(), String> {
+    Some(Input::<String>::new().with_prompt(prompt).default(default.to_string()).validate_with(|v: &String| -> Result<(), String> {
         if let Ok(v) = v.parse::<T>() {
             if range.contains(&v) {
                 Ok(())
             }
             else {
-                Err(format!("Value out of range"))
+                Err("Value out of range".to_owned())
             }
         }
         else {
             Err("It's not an number".to_owned())
         }
-    }).interact().expect("IO error").parse().expect("Value should be validated")
+    }).interact_text().or_cancel()?.parse().expect("Value should be validated"))
 }
 
 #[inline]
-pub fn get_path(prompt: &str) -> String {
-    Input::<String>::new().with_prompt(prompt).interact().expect("IO error")
+pub fn get_path(prompt: &str) -> Option<String> {
+    Input::<String>::new().with_prompt(prompt).interact_text().or_cancel()
 }
 
 #[inline]
 fn prepate_path() -> Option<Box<Path>> {
-    let target = get_path("File path");
+    confirm_overwrite(get_path("File path")?)
+}
+
+/// Existing target is only replaced once the new content is completely written
+fn confirm_overwrite(target: String) -> Option<Box<Path>> {
     let path = Path::new(&target);
-    if path.exists() {
-        if Confirm::new().with_prompt(format!("Path {target} already exists. Delete it?")).interact().expect("IO error") {
-            delete_path(path);
-        }
-        else {
-            return None;
-        }
+    if path.exists() && !Confirm::new().with_prompt(format!("Path {target} already exists. Overwrite it?")).interact().or_cancel()? {
+        return None;
     }
     Some(Box::from(path))
 }
 
-pub fn save<T: Serialize>(val: &T) -> bool {
+#[inline]
+pub fn save<T: Serialize + Fingerprint>(val: &T) -> bool {
+    save_with(val, save_to)
+}
+
+/// Same as `save`, but a key saved without password can be read only by its owner
+#[inline]
+pub fn save_private<T: Serialize + Fingerprint>(val: &T) -> bool {
+    save_with(val, save_private_to)
+}
+
+fn save_with<T: Serialize + Fingerprint>(val: &T, save_to: fn(&T, &Path, Option<&[u8]>) -> bool) -> bool {
     let path = match prepate_path() {
         Some(path) => path,
         None => return false,
     };
-    match match Confirm::new().with_prompt("With password?").interact().expect("IO error") {
-        true => serialize_serde(
-            val,
-            Password::new()
-                        .with_prompt("Passwrod")
-                        .with_confirmation("Repeat password", "Passwords are not the same")
-                        .interact()
-                        .expect("IO error")
-                        .as_bytes()
-        ),
-        false => serialize_serde_no_pass(val)
+    let saved = match Confirm::new().with_prompt("With password?").interact().or_cancel() {
+        Some(true) => match new_password() {
+            Some(password) => save_to(val, &path, Some(password.as_bytes())),
+            None => false,
+        },
+        Some(false) => save_to(val, &path, None),
+        None => false,
+    };
+    if saved {
+        println!("Key saved, fingerprint: {}", val.fingerprint());
+    }
+    saved
+}
+
+/// Keys with metadata are saved in `KeyFile`, the rest as they are
+pub fn save_key<T: Serialize + Fingerprint>(key: &T, metadata: Option<&KeyMetadata>) -> bool {
+    match metadata {
+        Some(metadata) => save(&KeyFile::new(key, metadata.clone())),
+        None => save(key),
+    }
+}
+
+/// Same as `save_key`, but a key saved without password can be read only by its owner
+pub fn save_private_key<T: Serialize + Fingerprint>(key: &T, metadata: Option<&KeyMetadata>) -> bool {
+    match metadata {
+        Some(metadata) => save_private(&KeyFile::new(key, metadata.clone())),
+        None => save_private(key),
+    }
+}
+
+#[inline]
+fn new_password() -> Option<String> {
+    Password::new()
+        .with_prompt("Password")
+        .with_confirmation("Repeat password", "Passwords are not the same")
+        .interact()
+        .or_cancel()
+}
+
+pub fn read_text(path: &Path) -> Option<String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(err) => {
+            println_error(&format!("Couldn't read {} - {err}", path.display()));
+            None
+        }
+    }
+}
+
+/// Shows fingerprint of a key decoded from a foreign format or reports why it couldn't be decoded
+pub fn decoded_key<T: Fingerprint, E: std::fmt::Display>(path: &Path, key: Result<T, E>) -> Option<T> {
+    match key {
+        Ok(key) => {
+            println!("Key loaded, fingerprint: {}", key.fingerprint());
+            Some(key)
+        }
+        Err(err) => {
+            println_error(&format!("Couldn't decode {} - {err}", path.display()));
+            None
+        }
+    }
+}
+
+/// Writes `text` to a file chosen by user, for formats which other tools read
+pub fn save_text(text: &str) -> bool {
+    match create_file() {
+        Some(file) => write_text(file, text),
+        None => false,
+    }
+}
+
+/// Same as `save_text`, but only the owner can read the saved file
+pub fn save_private_text(text: &str) -> bool {
+    match prepate_path().map(PendingFile::create_private) {
+        Some(Ok(file)) => write_text(file, text),
+        Some(Err(err)) => {
+            println_error(&format!("Couldn't create a file - {err}"));
+            false
+        }
+        None => false,
+    }
+}
+
+fn write_text(mut file: PendingFile, text: &str) -> bool {
+    if let Err(err) = file.file().write_all(text.as_bytes()) {
+        println_error(&format!("Couldn't write a file - {err}"));
+        return false;
+    }
+    match file.persist() {
+        Ok(()) => true,
+        Err(err) => {
+            println_error(&format!("Couldn't save a file - {err}"));
+            false
+        }
+    }
+}
+
+#[inline]
+pub fn save_to<T: Serialize>(val: &T, path: &Path, password: Option<&[u8]>) -> bool {
+    save_serialized(val, path, password, PendingFile::create)
+}
+
+/// Same as `save_to`, but a key saved without password can be read only by its owner
+#[inline]
+pub fn save_private_to<T: Serialize>(val: &T, path: &Path, password: Option<&[u8]>) -> bool {
+    match password {
+        Some(_) => save_to(val, path, password),
+        None => save_serialized(val, path, password, PendingFile::create_private),
+    }
+}
+
+fn save_serialized<T: Serialize>(val: &T, path: &Path, password: Option<&[u8]>, create: fn(Box<Path>) -> std::io::Result<PendingFile>) -> bool {
+    match match password {
+        Some(password) => serialize_serde(val, password),
+        None => serialize_serde_no_pass(val),
     } {
         Ok(data) => {
-            match File::create(path) {
+            match create(Box::from(path)) {
                 Ok(mut file) => {
-                    match file.write_all(&data) {
+                    match file.file().write_all(&data).and_then(|()| file.persist()) {
                         Ok(()) => true,
                         Err(err) => {
                             println_error(&format!("Couldn't save data to file - {}", err));
@@ -93,7 +292,7 @@ pub fn save<T: Serialize>(val: &T) -> bool {
 
 #[inline]
 pub fn check_path(prompt: &str) -> Option<Box<Path>> {
-    let src = get_path(prompt);
+    let src = get_path(prompt)?;
     let path = Path::new(&src);
     if !path.exists() {
         println_error(&format!("Path {src} does't exists"));
@@ -102,15 +301,7 @@ pub fn check_path(prompt: &str) -> Option<Box<Path>> {
     Some(Box::from(path))
 }
 
-pub fn read<T: for<'a> Deserialize<'a>>(prompt: &str) -> Option<T> {
-    let path = match check_path(prompt) {
-        Some(path) => path,
-        None => return None,
-    };
-    if !path.is_file() {
-        println_error(&format!("It's is not a file"));
-        return None;
-    }
+fn read_file(path: &Path) -> Option<Vec<u8>> {
     let mut buf = Vec::new();
     let mut file = match File::open(path) {
         Ok(file) => file,
@@ -123,37 +314,127 @@ pub fn read<T: for<'a> Deserialize<'a>>(prompt: &str) -> Option<T> {
         println_error(&format!("Unhandled error while reading file - {err}"));
         return None;
     }
-    let is_enc = match is_encrypted(&buf) {
-        Ok(ans) => ans,
+    Some(buf)
+}
+
+fn check_encryption(buf: &[u8]) -> Option<bool> {
+    match is_encrypted(buf) {
+        Ok(ans) => Some(ans),
         Err(serialize_with_password::Error::DataIsEmpty) => {
             println!("File is empty");
-            return None;
+            None
         }
         Err(err) => {
             println_error(&format!("Unexpected error while checking if file is encrypted - {err}"));
-            return None;
+            None
+        }
+    }
+}
+
+fn read_protected(path: &Path, password: Option<&[u8]>) -> Option<(Vec<u8>, Option<Vec<u8>>)> {
+    if !path.is_file() {
+        println_error(&format!("{} is not a file", path.display()));
+        return None;
+    }
+    let buf = read_file(path)?;
+    match (check_encryption(&buf)?, password) {
+        (true, Some(password)) => Some((buf, Some(password.to_vec()))),
+        (true, None) => {
+            println_error(&format!("{} is password protected", path.display()));
+            None
         }
+        (false, _) => Some((buf, None)),
+    }
+}
+
+/// Key files may be wrapped in `KeyFile`, its metadata is returned too
+fn decode<T: for<'a> Deserialize<'a>>(buf: &[u8], password: Option<&[u8]>) -> serialize_with_password::Result<(T, Option<KeyMetadata>)> {
+    let data = match password {
+        Some(password) => deserialize(buf, password)?,
+        None => deserialize_no_pass(buf)?,
     };
-    match is_enc {
+    key_metadata::decode(&data).map_err(serialize_with_password::Error::SerdeDecodingError)
+}
+
+fn decode_error(path: &Path, err: serialize_with_password::Error) {
+    match err {
+        serialize_with_password::Error::ChaCha20Error(_) => println_error(&format!("Wrong password for {}", path.display())),
+        err => println_error(&format!("Unhandled error while deserializing {} - {err}", path.display())),
+    }
+}
+
+#[inline]
+pub fn read_with_password<T: for<'a> Deserialize<'a>>(path: &Path, password: Option<&[u8]>) -> Option<T> {
+    read_key_with_password(path, password).map(|(key, _)| key)
+}
+
+pub fn read_key_with_password<T: for<'a> Deserialize<'a>>(path: &Path, password: Option<&[u8]>) -> Option<(T, Option<KeyMetadata>)> {
+    let (buf, password) = read_protected(path, password)?;
+    decode(&buf, password.as_deref()).map_err(|err| decode_error(path, err)).ok()
+}
+
+/// Reads `T` from `path`, falling back to reading `U` and converting it
+pub fn read_with_password_or<T, U, F>(path: &Path, password: Option<&[u8]>, convert: F) -> Option<T>
+    where T: for<'a> Deserialize<'a>, U: for<'a> Deserialize<'a>, F: FnOnce(U) -> T {
+    let (buf, password) = read_protected(path, password)?;
+    decode(&buf, password.as_deref())
+        .map(|(key, _)| key)
+        .or_else(|_| decode::<U>(&buf, password.as_deref()).map(|(key, _)| convert(key)))
+        .map_err(|err| decode_error(path, err))
+        .ok()
+}
+
+#[inline]
+pub fn read<T: for<'a> Deserialize<'a> + Fingerprint>(prompt: &str) -> Option<T> {
+    read_from(&check_path(prompt)?)
+}
+
+#[inline]
+pub fn read_key<T: for<'a> Deserialize<'a> + Fingerprint>(prompt: &str) -> Option<(T, Option<KeyMetadata>)> {
+    read_key_from(&check_path(prompt)?)
+}
+
+#[inline]
+pub fn read_from<T: for<'a> Deserialize<'a> + Fingerprint>(path: &Path) -> Option<T> {
+    read_key_from(path).map(|(key, _)| key)
+}
+
+/// Reads a key and shows its fingerprint and metadata, so it's clear which key has been loaded
+pub fn read_key_from<T: for<'a> Deserialize<'a> + Fingerprint>(path: &Path) -> Option<(T, Option<KeyMetadata>)> {
+    let (key, metadata) = decode_from::<T>(path)?;
+    println!("Key loaded, fingerprint: {}", key.fingerprint());
+    if let Some(metadata) = &metadata {
+        metadata.print();
+    }
+    Some((key, metadata))
+}
+
+fn decode_from<T: for<'a> Deserialize<'a>>(path: &Path) -> Option<(T, Option<KeyMetadata>)> {
+    if !path.is_file() {
+        println_error("It's is not a file");
+        return None;
+    }
+    let buf = read_file(path)?;
+    match check_encryption(&buf)? {
         true => {
-            let mut ans = deserialize_serde(
+            let mut ans = decode(
                 &buf,
-                Password::new()
+                Some(Password::new()
                             .with_prompt("Password")
                             .interact()
-                            .expect("IO error")
-                            .as_bytes()
+                            .or_cancel()?
+                            .as_bytes())
             );
             while let Err(serialize_with_password::Error::ChaCha20Error(_)) = ans {
-                ans = deserialize_serde(
+                ans = decode(
                     &buf,
-                    Password::new()
+                    Some(Password::new()
                                 .with_prompt("Wrong password")
                                 .interact()
-                                .expect("IO error")
-                                .as_bytes()
+                                .or_cancel()?
+                                .as_bytes())
                 );
-                if !Confirm::new().with_prompt("Try again?").default(true).interact().expect("IO error") {
+                if !Confirm::new().with_prompt("Try again?").default(true).interact().or_cancel()? {
                     return None;
                 }
             }
@@ -162,7 +443,7 @@ pub fn read<T: for<'a> Deserialize<'a>>(prompt: &str) -> Option<T> {
             }
             ans.ok()
         }
-        false => match deserialize_serde_no_pass(&buf) {
+        false => match decode(&buf, None) {
             Ok(ans) => Some(ans),
             Err(err) => {
                 println_error(&format!("Unhandled error while deserializing file - {err}"));
@@ -172,25 +453,128 @@ pub fn read<T: for<'a> Deserialize<'a>>(prompt: &str) -> Option<T> {
     }
 }
 
-pub fn create_signers_list() -> Option<SignersList> {
-    let path = match prepate_path() {
-        Some(path) => path,
-        None => return None,
+/// Fingerprint of whichever kind of key decrypted `data` holds, `None` if it isn't a key
+fn key_file_fingerprint(data: &[u8]) -> Option<String> {
+    key_metadata::decode::<PrivateKey>(data).map(|(key, _)| key.fingerprint())
+        .or_else(|_| key_metadata::decode::<PublicKey>(data).map(|(key, _)| key.fingerprint()))
+        .or_else(|_| key_metadata::decode::<RsaPrivateKey>(data).map(|(key, _)| key.fingerprint()))
+        .or_else(|_| key_metadata::decode::<RsaPublicKey>(data).map(|(key, _)| key.fingerprint()))
+        .ok()
+}
+
+/// Adds, changes or removes password of a key file
+/// Only the password layer is rewritten, so it works for every kind of key and the file is replaced atomically
+pub fn change_password() {
+    let Some(path) = check_path("Key file path") else {
+        return;
+    };
+    if !path.is_file() {
+        println_error(&format!("{} is not a file", path.display()));
+        return;
+    }
+    let Some(buf) = read_file(&path) else {
+        return;
+    };
+    let Some(encrypted) = check_encryption(&buf) else {
+        return;
     };
+    let key = match encrypted {
+        true => loop {
+            let Some(password) = Password::new().with_prompt("Current password").interact().or_cancel() else {
+                return;
+            };
+            match deserialize(&buf, password.as_bytes()) {
+                Ok(data) => break data,
+                Err(serialize_with_password::Error::ChaCha20Error(_)) => match Confirm::new().with_prompt("Wrong password. Try again?").default(true).interact().or_cancel() {
+                    Some(true) => continue,
+                    _ => return,
+                },
+                Err(err) => {
+                    println_error(&format!("Unhandled error while decrypting file - {err}"));
+                    return;
+                }
+            }
+        },
+        false => match deserialize_no_pass(&buf) {
+            Ok(data) => data,
+            Err(err) => {
+                println_error(&format!("Unhandled error while reading file - {err}"));
+                return;
+            }
+        },
+    };
+    let Some(fingerprint) = key_file_fingerprint(&key) else {
+        println_error(&format!("{} is not a key file", path.display()));
+        return;
+    };
+    println!("Key fingerprint: {fingerprint}");
+    let items: &[&str] = match encrypted {
+        true => &["Change password", "Remove password", "Exit"],
+        false => &["Add password", "Exit"],
+    };
+    let (data, done) = match Select::new()
+            .with_prompt(match encrypted {
+                true => "Key file is password protected",
+                false => "Key file has no password",
+            })
+            .items(items)
+            .default(0)
+            .interact()
+            .or_cancel()
+            .map(|pos| (encrypted, pos)) {
+        Some((_, 0)) => {
+            let Some(password) = new_password() else {
+                return;
+            };
+            match serialize(&key, password.as_bytes()) {
+                Ok(data) => (data, if encrypted { "changed" } else { "added" }),
+                Err(err) => {
+                    println_error(&format!("Unexpected error while encrypting key - {err}"));
+                    return;
+                }
+            }
+        }
+        Some((true, 1)) => (serialize_no_pass(&key), "removed"),
+        _ => return,
+    };
+    // Temporary file is created with default permissions, a password protected key file should keep its own
+    // and a key without password is readable only by its owner
+    let result = match done {
+        "removed" => PendingFile::create_private(path.clone()),
+        _ => std::fs::metadata(&path).and_then(|metadata| {
+            let file = PendingFile::create(path.clone())?;
+            std::fs::set_permissions(file.path(), metadata.permissions())?;
+            Ok(file)
+        }),
+    };
+    let result = result.and_then(|mut file| {
+        file.file().write_all(&data)?;
+        file.persist()
+    });
+    match result {
+        Ok(()) => println!("Password of {} has been {done}", path.display()),
+        Err(err) => println_error(&format!("Couldn't save key file - {err}")),
+    }
+}
+
+pub fn create_signers_list() -> Option<SignersList> {
+    let path = prepate_path()?;
+    delete_path(&path)?;
     if let Err(err) = create_dir(path) {
         println_error(&format!("Couldn't create a directory for signers list - {}", err));
         return None;
     }
-    SignersList::new(Path::new(&Input::<String>::new().with_prompt("File path").interact().expect("IO error"))).ok()
+    SignersList::new(Path::new(&get_path("File path")?)).ok()
 }
 
+#[inline]
 pub fn open_signer_list() -> Option<SignersList> {
-    let path = match check_path("Signers list path") {
-        Some(path) => path,
-        None => return None,
-    };
+    open_signer_list_at(&check_path("Signers list path")?)
+}
+
+pub fn open_signer_list_at(path: &Path) -> Option<SignersList> {
     if !path.is_dir() {
-        println_error(&format!("It's is not a directory"));
+        println_error("It's is not a directory");
         return None;
     }
     match SignersList::open(path) {
@@ -202,9 +586,55 @@ pub fn open_signer_list() -> Option<SignersList> {
     }
 }
 
+/// SHA-256 of RSA modulus followed by public exponent
+pub fn fingerprint(key: &RsaPublicKey) -> String {
+    let digest = Sha256::new()
+        .chain_update(key.n().to_bytes_be())
+        .chain_update(key.e().to_bytes_be())
+        .finalize();
+    format!("SHA256:{}", digest.iter().map(|byte| format!("{byte:02x}")).collect::<String>())
+}
+
+/// Keys identified by the fingerprint of their RSA public key, so every form of the same key shares it
+pub trait Fingerprint {
+    fn fingerprint(&self) -> String;
+}
+
+impl Fingerprint for RsaPublicKey {
+    fn fingerprint(&self) -> String {
+        fingerprint(self)
+    }
+}
+
+impl Fingerprint for RsaPrivateKey {
+    fn fingerprint(&self) -> String {
+        fingerprint(&self.to_public_key())
+    }
+}
+
+impl Fingerprint for PublicKey {
+    fn fingerprint(&self) -> String {
+        fingerprint(self.get_rsa_public_key())
+    }
+}
+
+impl Fingerprint for PrivateKey {
+    fn fingerprint(&self) -> String {
+        fingerprint(&self.get_rsa_public_key())
+    }
+}
+
 #[inline]
 pub fn get_private_key() -> Option<PrivateKey> {
-    read::<PrivateKey>("Private key path")
+    get_private_key_file().map(|(key, _)| key)
+}
+
+/// Same as `get_private_key`, but keeps metadata of the key file
+pub fn get_private_key_file() -> Option<(PrivateKey, Option<KeyMetadata>)> {
+    if let Some(key) = keyring::select_private_key_file()? {
+        return Some(key);
+    }
+    keyring::read_private_key_file(&check_path("Private key path")?)
 }
 
 pub fn get_public_key() -> Option<PublicKey> {
@@ -217,26 +647,33 @@ pub fn get_public_key() -> Option<PublicKey> {
             .default(0)
             .with_prompt("Public key source")
             .interact()
-            .expect("IO error") {
+            .or_cancel()? {
         0 => read::<PublicKey>("Public key path"),
-        1 => read::<PrivateKey>("Private key path").map(|key: PrivateKey| key.get_public_key()),
+        1 => get_private_key().map(|key: PrivateKey| key.get_public_key()),
         _ => None,
     }
 }
 
 pub fn get_private_rsa_key() -> Option<RsaPrivateKey> {
+    if let Some(key) = keyring::select_private_rsa_key()? {
+        return Some(key);
+    }
     match Select::new()
             .items(&[
                 "From RSA private key",
                 "From private key",
+                "From PEM file (PKCS#8 or PKCS#1)",
+                "From OpenSSH private key",
                 "Exit",
             ])
             .default(0)
             .with_prompt("Private RSA key source")
             .interact()
-            .expect("IO error") {
-        0 => read::<RsaPrivateKey>("Private RSA key path"),
-        1 => read::<PrivateKey>("Private key path").map(|key: PrivateKey| key.get_rsa_private_key().to_owned()),
+            .or_cancel()? {
+        0 => keyring::read_private_rsa_key(&check_path("Private RSA key path")?),
+        1 => keyring::read_private_key(&check_path("Private key path")?).map(|key: PrivateKey| key.get_rsa_private_key().to_owned()),
+        2 => pem::read_private_rsa_key(&check_path("PEM file path")?),
+        3 => openssh::read_private_rsa_key(&check_path("OpenSSH private key path")?),
         _ => None,
     }
 }
@@ -248,40 +685,43 @@ pub fn get_public_rsa_key() -> Option<RsaPublicKey> {
                 "From RSA private key",
                 "From private key",
                 "From public key",
+                "From PEM file (SPKI or PKCS#1)",
+                "From OpenSSH public key (.pub or authorized_keys)",
                 "Exit",
             ])
             .default(0)
             .with_prompt("Public RSA key source")
             .interact()
-            .expect("IO error") {
+            .or_cancel()? {
         0 => read::<RsaPublicKey>("Public RSA key path"),
         1 => read::<RsaPrivateKey>("Private RSA key path").map(|key: RsaPrivateKey| key.to_public_key()),
-        2 => read::<PrivateKey>("Private key path").map(|key: PrivateKey| key.get_rsa_public_key()),
+        2 => get_private_key().map(|key: PrivateKey| key.get_rsa_public_key()),
         3 => read::<PublicKey>("Public key path").map(|key: PublicKey| key.get_rsa_public_key().to_owned()),
+        4 => pem::read_public_rsa_key(&check_path("PEM file path")?),
+        5 => openssh::read_public_rsa_key(&check_path("OpenSSH public key path")?),
         _ => None,
     }
 }
 
 #[inline]
-pub fn create_file() -> Option<File> {
-    File::create(prepate_path()?).ok()
+fn pending_file(path: Box<Path>) -> Option<PendingFile> {
+    match PendingFile::create(path) {
+        Ok(file) => Some(file),
+        Err(err) => {
+            println_error(&format!("Couldn't create a file - {err}"));
+            None
+        }
+    }
 }
 
 #[inline]
-pub fn create_file_with_default(value: String) -> Option<File> {
-    File::create({
-        let target = Input::<String>::new().with_prompt("File path").default(value).interact().expect("IO error");
-        let path = Path::new(&target);
-        if path.exists() {
-            if Confirm::new().with_prompt(format!("Path {target} already exists. Delete it?")).interact().expect("IO error") {
-                delete_path(path);
-            }
-            else {
-                return None;
-            }
-        }
-        Box::from(path)
-    }).ok()
+pub fn create_file() -> Option<PendingFile> {
+    pending_file(prepate_path()?)
+}
+
+#[inline]
+pub fn create_file_with_default(value: String) -> Option<PendingFile> {
+    pending_file(confirm_overwrite(Input::<String>::new().with_prompt("File path").default(value).interact_text().or_cancel()?)?)
 }
 
 #[inline]
@@ -289,12 +729,25 @@ pub fn open_file(prompt: &str) -> Option<File> {
     File::open(check_path(prompt)?).ok()
 }
 
-pub fn create_encrypted_file() -> Option<EncryptedFile> {
-    match EncryptedFile::new(match prepate_path() {
-        Some(path) => path,
-        None => return None,
-    }) {
-        Ok(ef) => Some(ef),
+/// Creates a new archive next to `path` and moves it over `path` once it's valid
+pub fn create_encrypted_file_at(path: &Path) -> std::io::Result<EncryptedFile> {
+    let temp = temp_path(path);
+    let ans = EncryptedFile::new(&temp).and_then(|ef| {
+        File::open(&temp)?.sync_all()?;
+        rename(&temp, path)?;
+        Ok(ef)
+    });
+    if ans.is_err() {
+        let _ = remove_file(&temp);
+    }
+    sync_parent(path);
+    ans
+}
+
+pub fn create_encrypted_file() -> Option<(Box<Path>, EncryptedFile)> {
+    let path = prepate_path()?;
+    match create_encrypted_file_at(&path) {
+        Ok(ef) => Some((path, ef)),
         Err(err) => {
             println_error(&format!("Unhandled error while trying to create encrypted file - {err}"));
             None
@@ -302,19 +755,19 @@ pub fn create_encrypted_file() -> Option<EncryptedFile> {
     }
 }
 
-pub fn open_encrypted_file() -> Option<EncryptedFile> {
+pub fn open_encrypted_file() -> Option<(Box<Path>, EncryptedFile)> {
     let path = match check_path("Encrypted file path") {
         Some(path) => {
             if !path.is_file() {
-                println_error(&format!("It's not a file"));
+                println_error("It's not a file");
                 return None;
             }
             path
         }
         None => return None,
     };
-    match EncryptedFile::new(path) {
-        Ok(ef) => Some(ef),
+    match EncryptedFile::new(&path) {
+        Ok(ef) => Some((path, ef)),
         Err(err) => {
             println_error(&format!("Unhandled error while trying to open encrypted file - {err}"));
             None
@@ -322,52 +775,17 @@ pub fn open_encrypted_file() -> Option<EncryptedFile> {
     }
 }
 
-const STRAIGHT_RIGHT: char = '├';
-const STRAIGHT: char = '│';
-const UP_RIGHT: char = '└';
-const SPACE: char = ' ';
-const DIRECTORY_PREFIX: &str = "<DIR>";
-const FILE_PREFIX: &str = "<FILE>";
-
-fn list_content_helper(content: &DirectoryContent, prefix: &str) {
-    let last_file = content.get_files_iter().rev().take(1).next();
-    let last_dir = if last_file.is_some() {
-        None
-    }
-    else {
-        content.get_dir_iter().rev().take(1).next()
-    };
-    for (name, dir) in content.get_dir_iter().rev().skip(last_dir.is_some() as usize).rev() {
-        println!("{prefix}{STRAIGHT_RIGHT}{DIRECTORY_PREFIX} {name}");
-        list_content_helper(dir, &format!("{prefix}{STRAIGHT}"));
-    }
-    if let Some((name, dir)) = last_dir {
-        println!("{prefix}{UP_RIGHT}{DIRECTORY_PREFIX} {name}");
-        list_content_helper(dir, &format!("{prefix}{SPACE}"));
-    }
-    for (name, file) in content.get_files_iter().rev().skip(1).rev() {
-        println!("{prefix}{STRAIGHT_RIGHT}{FILE_PREFIX} {name} has_content: {}, has_key: {}, has_digest: {}, has_signature: {}", file.has_content(), file.has_key(), file.has_digest(), file.is_signed());
-    }
-    if let Some((name, file)) = last_file {
-        println!("{prefix}{UP_RIGHT}{FILE_PREFIX} {name} has_content: {}, has_key: {}, has_digest: {}, has_signature: {}", file.has_content(), file.has_key(), file.has_digest(), file.is_signed());
-    }
-}
-
-pub fn list_content(content: &DirectoryContent) {
-    list_content_helper(content, "");
-    println!();
-}
-
-pub fn get_zip_file_options() -> FileOptions {
+/// Returns chosen options together with their description, as they can't be read back from `FileOptions`
+pub fn get_zip_file_options() -> Option<(FileOptions, String)> {
     use the_lock_lib::CompressionMethod;
     loop {
-        return match Confirm::new()
+        return Some(match Confirm::new()
                 .with_prompt("Use default zip file options")
                 .interact()
-                .expect("IO error") {
-            true => FileOptions::default(),
+                .or_cancel()? {
+            true => (FileOptions::default(), "Default".to_owned()),
             false => {
-                match Select::new()
+                let (method, levels, default) = match Select::new()
                         .with_prompt("Compression method")
                         .items(&[
                             "Deflated",
@@ -378,22 +796,37 @@ pub fn get_zip_file_options() -> FileOptions {
                         ])
                         .default(0)
                         .interact()
-                        .expect("IO error") {
-                    0 => FileOptions::default()
-                            .compression_method(CompressionMethod::Deflated)
-                            .compression_level(Some(get_number_in_range("Compression rate [0;9]", 0..=9, 6))),
-                    1 => FileOptions::default()
-                            .compression_method(CompressionMethod::Stored),
-                    2 => FileOptions::default()
-                            .compression_method(CompressionMethod::Bzip2)
-                            .compression_level(Some(get_number_in_range("Compression rate [0;9]", 0..=9, 6))),
-                    3 => FileOptions::default()
-                            .compression_method(CompressionMethod::Bzip2)
-                            .compression_level(Some(get_number_in_range("Compression rate [-7;22]", -7..=22, 3))),               
+                        .or_cancel()? {
+                    0 => (CompressionMethod::Deflated, 0..=9, 6),
+                    1 => return Some((FileOptions::default().compression_method(CompressionMethod::Stored), "Stored".to_owned())),
+                    2 => (CompressionMethod::Bzip2, 0..=9, 6),
+                    3 => (CompressionMethod::Zstd, -7..=22, 3),
                     _ => continue,
-                }
+                };
+                let level = get_number_in_range(&format!("Compression rate [{};{}]", levels.start(), levels.end()), levels, default)?;
+                (FileOptions::default().compression_method(method).compression_level(Some(level)), format!("{method}, level {level}"))
             }
-        };
+        });
+    }
+}
+
+/// Table with columns aligned to the longest cell, rows marked as failed are highlighted
+pub fn print_table<const N: usize>(header: [&str; N], rows: &[([String; N], bool)]) {
+    let mut widths = header.map(|cell| cell.chars().count());
+    for (row, _) in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: [&str; N]| cells.iter().zip(widths).map(|(cell, width)| format!("{cell:<width$}")).collect::<Vec<_>>().join("  ");
+    println!("{}", line(header));
+    println!("{}", widths.map(|width| "-".repeat(width)).join("  "));
+    for (row, failed) in rows {
+        let text = line(row.each_ref().map(String::as_str));
+        match failed {
+            true => println!("{}", error_font(&text)),
+            false => println!("{text}"),
+        }
     }
 }
 
@@ -410,5 +843,5 @@ pub fn green_font(msg: &str) -> console::StyledObject<&str> {
 // TODO do it as macro
 #[inline]
 pub fn println_error(msg: &str) {
-    println!("{}", error_font(msg));
+    eprintln!("{}", error_font(msg));
 }